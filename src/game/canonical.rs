@@ -0,0 +1,96 @@
+use crate::game::stack::constraint::Constraint;
+use crate::game::stack::Stack;
+use crate::game::Game;
+use crate::line_reader::LineReader;
+use crate::renderer::Renderer;
+
+// Seeds the Zobrist keys, changing it changes every hash.
+const ZOBRIST_SEED: u64 = 0x5EED_2026_50C7_1A6E;
+
+// Splitmix64 finalizer, used to derive Zobrist keys on the fly instead of storing a table.
+fn mix(value: u64) -> u64 {
+    let mut z: u64 = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn zobrist_key(capacity: usize, slot: usize, unit_id: usize) -> u64 {
+    mix(mix(mix(ZOBRIST_SEED ^ capacity as u64) ^ slot as u64) ^ unit_id as u64)
+}
+
+impl Stack {
+    /// Zobrist hash of a single stack: one key per occupied slot, plus one for the capacity
+    /// and constraint so that empty stacks of different sizes or constraints hash differently.
+    pub fn zobrist_hash(&self) -> u64 {
+        let capacity: usize = self.get_capacity();
+        let base: u64 = match self.get_constraint() {
+            None => zobrist_key(capacity, capacity, 0),
+            Some(Constraint::Locked(kind_id)) => zobrist_key(capacity, capacity + 1, kind_id),
+            Some(Constraint::Capped(kind_id)) => zobrist_key(capacity, capacity + 2, kind_id),
+        };
+        self.iter_unit_ids()
+            .enumerate()
+            .fold(base, |hash, (slot, unit_id)| {
+                hash ^ zobrist_key(capacity, slot, unit_id)
+            })
+    }
+}
+
+/// Sorts the stacks so that boards differing only by the order of their stacks are equal.
+pub fn canonical_stacks(stacks: &[Stack]) -> Vec<Stack> {
+    let mut canonical: Vec<Stack> = stacks.to_vec();
+    canonical.sort();
+    canonical
+}
+
+/// Stable hash of a board, invariant under permutations of its stacks.
+/// Stack hashes are summed rather than xored, so two identical stacks don't cancel out.
+pub fn canonical_hash(stacks: &[Stack]) -> u64 {
+    stacks
+        .iter()
+        .map(Stack::zobrist_hash)
+        .fold(0, u64::wrapping_add)
+}
+
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
+    pub fn canonical_stacks(&self) -> Vec<Stack> {
+        canonical_stacks(&self.stacks)
+    }
+
+    pub fn canonical_hash(&self) -> u64 {
+        canonical_hash(&self.stacks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permuted_stacks_are_equivalent() {
+        let board = Stack::board_from_vecs(vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]]);
+        let permuted = Stack::board_from_vecs(vec![vec![2, 0], vec![2, 1, 0], vec![1, 2]]);
+
+        assert!(canonical_stacks(&board) == canonical_stacks(&permuted));
+        assert_eq!(canonical_hash(&board), canonical_hash(&permuted));
+    }
+
+    #[test]
+    fn test_distinct_boards_hash_differently() {
+        let board = Stack::board_from_vecs(vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]]);
+        let swapped_units = Stack::board_from_vecs(vec![vec![1, 2, 0], vec![1, 2], vec![2, 0]]);
+        let other_capacity = Stack::board_from_vecs(vec![vec![2, 1], vec![1, 2, 0], vec![2, 0]]);
+
+        assert_ne!(canonical_hash(&board), canonical_hash(&swapped_units));
+        assert_ne!(canonical_hash(&board), canonical_hash(&other_capacity));
+    }
+
+    #[test]
+    fn test_identical_stacks_do_not_cancel() {
+        let ones = Stack::board_from_vecs(vec![vec![1, 0], vec![1, 0]]);
+        let twos = Stack::board_from_vecs(vec![vec![2, 0], vec![2, 0]]);
+
+        assert_ne!(canonical_hash(&ones), canonical_hash(&twos));
+    }
+}
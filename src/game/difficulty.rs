@@ -0,0 +1,191 @@
+use crate::game::solver::{SearchStats, Solution, Solver, StackMove};
+use crate::game::stack::Stack;
+use crate::game::Game;
+use crate::line_reader::LineReader;
+use crate::renderer::Renderer;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label: &str = match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+pub struct DifficultyReport {
+    pub difficulty: Difficulty,
+    pub score: usize,
+    pub solution_length: Option<usize>,
+    pub optimal: bool,
+    pub branching_factor: f64,
+    pub dead_ends: usize,
+    pub buffer_moves: usize,
+}
+
+// Maps a metric onto 0..=3 points, one point per threshold reached.
+fn points(value: f64, thresholds: [f64; 3]) -> usize {
+    thresholds
+        .iter()
+        .filter(|threshold| value >= **threshold)
+        .count()
+}
+
+// Counts the moves of a solution that land on an empty stack.
+fn count_buffer_moves(solver: &Solver, stacks: &[Stack], moves: &[StackMove]) -> usize {
+    let mut board: Vec<Stack> = stacks.to_vec();
+    let mut buffer_moves: usize = 0;
+    for stack_move in moves {
+        if board[stack_move.1].is_vacant() {
+            buffer_moves += 1;
+        }
+        board = solver.apply_move(&board, *stack_move);
+    }
+    buffer_moves
+}
+
+impl DifficultyReport {
+    pub fn new(
+        solver: &Solver,
+        stacks: &[Stack],
+        solution: Option<Solution>,
+        stats: SearchStats,
+    ) -> DifficultyReport {
+        let branching_factor: f64 = match stats.explored {
+            0 => 0.0,
+            explored => stats.legal_moves as f64 / explored as f64,
+        };
+        let dead_end_ratio: f64 = match stats.explored {
+            0 => 0.0,
+            explored => stats.dead_ends as f64 / explored as f64,
+        };
+        let (solution_length, optimal, buffer_moves) = match &solution {
+            Some(solution) => (
+                Some(solution.moves.len()),
+                solution.optimal,
+                count_buffer_moves(solver, stacks, &solution.moves),
+            ),
+            None => (None, false, 0),
+        };
+
+        let score: usize = match solution_length {
+            Some(length) => {
+                points(length as f64, [6.0, 16.0, 31.0])
+                    + points(branching_factor, [4.0, 8.0, 16.0])
+                    + points(dead_end_ratio, [0.01, 0.05, 0.15])
+                    + points(buffer_moves as f64, [1.0, 3.0, 6.0])
+            }
+            None => 12, // Unsolved within the node limit, rate it as hard as possible.
+        };
+        let difficulty: Difficulty = match score {
+            0..=2 => Difficulty::Easy,
+            3..=5 => Difficulty::Medium,
+            6..=8 => Difficulty::Hard,
+            _ => Difficulty::Expert,
+        };
+
+        DifficultyReport {
+            difficulty,
+            score,
+            solution_length,
+            optimal,
+            branching_factor,
+            dead_ends: stats.dead_ends,
+            buffer_moves,
+        }
+    }
+}
+
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
+    pub fn rate_difficulty(&self) -> DifficultyReport {
+        let solver: Solver = Solver::new(self.units_per_kind.clone(), self.settings.rule_set);
+        let (solution, stats) = solver.search(&self.stacks);
+        DifficultyReport::new(&solver, &self.stacks, solution, stats)
+    }
+
+    /// Keeps the stage's difficulty, and falls back to the solver's move count when no par was
+    /// authored.
+    pub fn rate(&mut self) -> DifficultyReport {
+        let report: DifficultyReport = self.rate_difficulty();
+        self.difficulty = Some(report.difficulty);
+        // A solution found after the exact search gave up is no baseline to rate players against.
+        if self.settings.par.is_none() && report.optimal {
+            self.settings.par = report.solution_length;
+        }
+        report
+    }
+
+    /// Orders stages from easiest to hardest, keeping the original order between equal scores.
+    /// Stages keep their rating, so that it isn't computed again before they are played.
+    pub fn sort_by_difficulty(stages: Vec<Game<TLR, TRD>>) -> Vec<Game<TLR, TRD>> {
+        let mut rated: Vec<((Difficulty, usize), Game<TLR, TRD>)> = stages
+            .into_iter()
+            .map(|mut stage| {
+                let report: DifficultyReport = stage.rate();
+                ((report.difficulty, report.score), stage)
+            })
+            .collect();
+        rated.sort_by_key(|(key, _)| *key);
+        rated.into_iter().map(|(_, stage)| stage).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_reader::MockLineReader;
+
+    #[test]
+    fn test_first_stage_is_easy() {
+        let stages: Vec<Game<MockLineReader>> = Game::get_stages(MockLineReader::default());
+        let report: DifficultyReport = stages[0].rate_difficulty();
+
+        assert_eq!(report.difficulty, Difficulty::Easy);
+        assert_eq!(report.solution_length, Some(3));
+        assert!(report.optimal);
+        assert_eq!(report.buffer_moves, 0);
+    }
+
+    #[test]
+    fn test_sort_by_difficulty() {
+        let boards: [(&str, Vec<Vec<usize>>); 3] = [
+            (
+                "Long",
+                vec![
+                    vec![3, 2, 1, 0],
+                    vec![1, 3, 2, 0],
+                    vec![2, 1, 3, 0],
+                    vec![0, 0, 0, 0],
+                ],
+            ),
+            ("Short", vec![vec![1, 0], vec![1, 0]]),
+            ("Middle", vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]]),
+        ];
+        let stages: Vec<Game<MockLineReader>> = boards
+            .into_iter()
+            .map(|(name, vecs)| {
+                Game::new_from_vecs(vecs, Some(name.to_string()), MockLineReader::default())
+            })
+            .collect();
+        let sorted: Vec<Game<MockLineReader>> = Game::sort_by_difficulty(stages);
+
+        let names: Vec<&str> = sorted
+            .iter()
+            .map(|stage| stage.stage_name.as_str())
+            .collect();
+        assert_eq!(names, ["Short", "Middle", "Long"]);
+        assert!(sorted.iter().all(|stage| stage.difficulty.is_some()));
+    }
+}
@@ -1,9 +1,19 @@
 use crate::game::stack::kind::Kind;
+use crate::game::stack::Stack;
 
 #[derive(Clone)]
-pub struct Entry {
-    pub from: usize,
-    pub to: usize,
-    pub _kind: Kind,
-    pub quantity: usize,
+pub enum Entry {
+    Move {
+        from: usize,
+        to: usize,
+        _kind: Kind,
+        quantity: usize,
+    },
+    /// An empty stack appended by the power-up, always the last one on the board.
+    ExtraStack,
+    /// The board, and which of its units were hidden, as they were before a shuffle.
+    Shuffle {
+        previous: Vec<Stack>,
+        hidden: Vec<usize>,
+    },
 }
@@ -0,0 +1,118 @@
+use crate::game::solver::{Solver, StackMove};
+use crate::game::stack::kind::Kind;
+use crate::game::stack::Stack;
+use crate::game::Game;
+use crate::line_reader::LineReader;
+use crate::renderer::Renderer;
+
+// Hints are asked for mid-turn, so they get a small search and fall back to a greedy move.
+const HINT_NODE_LIMIT: usize = 2_000;
+
+/// Units fog hides in a freshly concealed stack, every one below the top run.
+pub fn units_below_top(stack: &Stack) -> usize {
+    stack.get_occupancy() - stack.get_top_unit_quantity()
+}
+
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
+    pub fn conceal_stacks(&mut self) {
+        self.hidden = self.stacks.iter().map(units_below_top).collect();
+    }
+
+    /// Exposes the units of the stack that are no longer covered.
+    pub fn reveal_top(&mut self, stack_ind: usize) {
+        let covered: usize = units_below_top(&self.stacks[stack_ind]);
+        self.hidden[stack_ind] = self.hidden[stack_ind].min(covered);
+    }
+
+    fn fog_hides_units(&self) -> bool {
+        self.hidden.iter().any(|hidden| *hidden > 0)
+    }
+
+    /// The board as the player sees it, with hidden units replaced by unknown ones.
+    pub fn known_stacks(&self) -> Vec<Stack> {
+        self.stacks
+            .iter()
+            .zip(&self.hidden)
+            .map(|(stack, hidden)| stack.masked(*hidden, Kind::get_unknown_id()))
+            .collect()
+    }
+
+    /// Suggests a move, from the solver when the whole board is known,
+    /// or from the visible units alone while fog hides some of them.
+    pub fn hint(&self) -> Option<StackMove> {
+        if !self.fog_hides_units() {
+            let solver: Solver = Solver::new(self.units_per_kind.clone(), self.settings.rule_set)
+                .with_node_limit(HINT_NODE_LIMIT);
+            if let Some(solution) = solver.search(&self.stacks).0 {
+                return solution.moves.first().copied();
+            }
+        }
+        self.greedy_hint()
+    }
+
+    // Prefers moves that uncover hidden units, then ones that join identical units,
+    // and avoids shuffling a sorted stack into an empty one.
+    fn greedy_hint(&self) -> Option<StackMove> {
+        let known: Vec<Stack> = self.known_stacks();
+        let rule_set = self.settings.rule_set;
+        rule_set
+            .legal_moves(&known)
+            .into_iter()
+            .max_by_key(|&(from, to)| {
+                let quantity: usize = rule_set.movable_quantity(&known, from, to);
+                let empties_run: bool = quantity == known[from].get_top_unit_quantity();
+                let uncovers: bool = empties_run && self.hidden[from] > 0;
+                let joins: bool = !known[to].is_vacant();
+                let shuffles: bool = empties_run && known[from].count_runs() == 1 && !joins;
+                (uncovers, joins, !shuffles)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_reader::MockLineReader;
+
+    #[test]
+    fn test_fog_reveals_uncovered_units() {
+        let mut stage: Game<MockLineReader> = Game::new_from_vecs(
+            vec![vec![1, 2, 2, 0], vec![2, 1, 0, 0], vec![0, 0, 0]],
+            None,
+            MockLineReader::default(),
+        );
+        let before: Vec<Stack> = stage.stacks.clone();
+        stage.conceal_stacks();
+        assert_eq!(
+            stage.stacks, before,
+            "Fog doesn't change the stacks themselves"
+        );
+        assert_eq!(stage.hidden, vec![1, 1, 0]);
+        assert_eq!(
+            stage.known_stacks()[0]
+                .iter_unit_ids()
+                .collect::<Vec<usize>>(),
+            vec![Kind::get_unknown_id(), 2, 2]
+        );
+
+        stage.move_legally(0, 2);
+        assert_eq!(stage.hidden[0], 0);
+        stage.undo_move();
+        assert_eq!(stage.hidden[0], 0, "Uncovered units stay revealed");
+    }
+
+    #[test]
+    fn test_hint_under_fog_prefers_uncovering() {
+        let mut stage: Game<MockLineReader> = Game::new_from_vecs(
+            vec![vec![1, 2, 0], vec![2, 1, 0], vec![0, 0, 0]],
+            None,
+            MockLineReader::default(),
+        );
+        assert!(stage.hint().is_some());
+
+        stage.conceal_stacks();
+        let (from, to) = stage.hint().unwrap();
+        assert!(stage.hidden[from] > 0);
+        assert!(stage.move_is_legal(from, to));
+    }
+}
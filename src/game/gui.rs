@@ -1,96 +1,351 @@
+use crate::game::rules::{constraint_violated, kind_is_complete, RuleSet};
+use crate::game::settings::{KindMetadata, UndoPolicy};
+use crate::game::stack::constraint::Constraint;
+use crate::game::stack::kind::{IsJoker, Kind, KindId};
+use crate::game::stack::Stack;
 use crate::game::Game;
 use crate::line_reader::LineReader;
-use std::io::{self, Write};
+use crate::profile::format_duration;
+use crate::profile::leaderboard::{self, Score};
+use crate::renderer::{display_width, Renderer};
+use std::io;
+use std::time::Duration;
+
+// How often the clock is redrawn while waiting for input.
+const CLOCK_TICK: Duration = Duration::from_secs(1);
+
+// Screen row of the status line printed by `render`.
+const STATUS_ROW: usize = 5;
 
 pub enum MenuOption {
+    ExtraStack,
     Help,
+    InputClosed,
     Move,
     Reset,
+    Shuffle,
+    Stats,
     Undo,
     Quit,
+    TimeUp,
+}
+
+/// A validated move, `quantity` is set when the player picked a count such as 'x2'.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlannedMove {
+    pub from: usize,
+    pub to: usize,
+    pub quantity: Option<usize>,
 }
 
 pub struct UserInput {
     pub menu_option: MenuOption,
-    pub stack_move: Option<(usize, usize)>,
+    pub moves: Vec<PlannedMove>,
+    /// Why the rest of a sequence of moves was rejected, to show once the valid ones are made.
+    pub error: Option<String>,
 }
 
 impl UserInput {
     pub fn new_menu_option(menu_option: MenuOption) -> UserInput {
         UserInput {
-            menu_option: menu_option,
-            stack_move: None,
+            menu_option,
+            moves: Vec::new(),
+            error: None,
         }
     }
 }
 
-impl<TLR: LineReader + Default> Game<TLR> {
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
     pub fn render(&self) {
-        // Clear the screen and move the cursor to the top-left corner
-        print!("\x1B[2J\x1B[H");
-        io::stdout().flush().unwrap(); // Ensure the screen is cleared immediately
-        println!("|**************|\n| Sorting Game |\n****************");
-        println!("{}", self.stage_name); // Display the current game name
-        println!("Turn - {}", self.turn);
-        println!();
+        self.renderer.clear_screen();
+        self.renderer
+            .println("|**************|\n| Sorting Game |\n****************");
+        match self.difficulty {
+            Some(difficulty) => {
+                self.renderer
+                    .println(&format!("{} ({})", self.stage_name, difficulty));
+            }
+            None => self.renderer.println(&self.stage_name), // Display the current game name
+        }
+        self.renderer.println(&self.status_line());
+        self.renderer.println("");
 
         for (stack_ind, stack) in self.stacks.iter().enumerate() {
             let mut buffer: String = "".to_string();
-            for unit_id in stack.iter_unit_ids() {
-                let unit_index: usize = self.get_kind_index(unit_id);
-                let color: [u8; 3] = COLORS[unit_index % COLORS.len()].clone();
-                buffer.push_str(
-                    format!(
-                        "\x1b[38;2;{};{};{}m{:>2}\x1b[0m ",
-                        color[0], color[1], color[2], unit_id,
-                    )
-                    .as_str(),
-                );
+            for (slot, unit_id) in stack.iter_unit_ids().enumerate() {
+                if slot < self.hidden[stack_ind] {
+                    buffer.push_str("?? ");
+                    continue;
+                }
+                if unit_id.is_joker() {
+                    buffer.push_str(" * ");
+                    continue;
+                }
+                // Padded by columns rather than chars, so that wide glyphs line up too.
+                let glyph: String = self.kind_glyph(unit_id);
+                let padding: String = " ".repeat(2_usize.saturating_sub(display_width(&glyph)));
+                if !self.renderer.uses_color() {
+                    buffer.push_str(&format!("{}{} ", padding, glyph));
+                    continue;
+                }
+                let color: [u8; 3] = self.kind_color(unit_id);
+                buffer.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m{}{}\x1b[0m ",
+                    color[0], color[1], color[2], padding, glyph,
+                ));
             }
             for _ in 0..stack.get_vacancy() {
                 buffer.push_str("__ ");
             }
-            println!("{:>2}: {}", stack_ind + 1, buffer);
+            match stack.get_constraint() {
+                Some(Constraint::Locked(kind_id)) if !kind_is_complete(&self.stacks, kind_id) => {
+                    let label: String = self.kind_label(kind_id);
+                    buffer.push_str(&format!(" [locked until {} is sorted]", label));
+                }
+                Some(Constraint::Capped(kind_id)) => {
+                    buffer.push_str(&format!(" [only {}]", self.kind_label(kind_id)));
+                }
+                _ => {}
+            }
+            self.renderer
+                .println(&format!("{:>2}: {}", stack_ind + 1, buffer));
+        }
+        self.renderer.println("");
+        if !self.settings.kinds.is_empty() {
+            let legend: Vec<String> = self
+                .settings
+                .kinds
+                .iter()
+                .map(|(kind_id, metadata)| {
+                    format!("{} {}", self.kind_glyph(*kind_id).trim(), metadata.name)
+                })
+                .collect();
+            self.renderer.println(&legend.join("    "));
+            self.renderer.println("");
+        }
+    }
+
+    // The stage's colour for the kind, or one from the palette.
+    fn kind_color(&self, kind_id: KindId) -> [u8; 3] {
+        match self
+            .settings
+            .kinds
+            .get(&kind_id)
+            .and_then(|metadata| metadata.color)
+        {
+            Some(color) => color,
+            None => COLORS[self.get_kind_index(kind_id) % COLORS.len()],
+        }
+    }
+
+    // What stands for a unit of the kind on the board, its glyph or its id.
+    fn kind_glyph(&self, kind_id: KindId) -> String {
+        match self.settings.kinds.get(&kind_id) {
+            Some(KindMetadata {
+                glyph: Some(glyph), ..
+            }) => glyph.clone(),
+            _ => kind_id.to_string(),
+        }
+    }
+
+    /// How the kind is referred to in messages, its name or its id.
+    pub fn kind_label(&self, kind_id: KindId) -> String {
+        match self.settings.kinds.get(&kind_id) {
+            Some(metadata) => metadata.name.clone(),
+            None => kind_id.to_string(),
+        }
+    }
+
+    pub fn status_line(&self) -> String {
+        let mut turn: String = format!("Turn - {}", self.turn);
+        if let Some(moves_left) = self.moves_left() {
+            turn.push_str(&format!("    Moves left - {}", moves_left));
+        }
+        let elapsed: Duration = self.started_at.elapsed();
+        match self.settings.time_limit {
+            Some(limit) => {
+                // Round the countdown up, so that it reads 0:00 only once time is up.
+                let remaining: Duration = limit.saturating_sub(elapsed);
+                let seconds: u64 = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                format!(
+                    "{}    Time left - {}",
+                    turn,
+                    format_duration(Duration::from_secs(seconds))
+                )
+            }
+            None if self.settings.clock => {
+                format!("{}    Time - {}", turn, format_duration(elapsed))
+            }
+            None => turn,
         }
-        println!();
     }
 
-    pub fn stage_complete_prompt(&self, is_last_stage: bool) {
+    // Redraws the status line in place, without disturbing what the player is typing.
+    fn tick_clock(&self) {
+        self.renderer.redraw_line(STATUS_ROW, &self.status_line());
+    }
+
+    // Reads a line while keeping the clock ticking, returns `None` if time runs out first.
+    fn read_line_ticking(&self, input: &mut String) -> Option<io::Result<usize>> {
+        let start: usize = input.len();
+        let result: io::Result<usize> =
+            if !self.settings.clock && self.settings.time_limit.is_none() {
+                self.line_reader.read_line(input)
+            } else {
+                loop {
+                    if self.time_is_up() {
+                        return None;
+                    }
+                    if let Some(result) = self.line_reader.read_line_timeout(input, CLOCK_TICK) {
+                        if self.time_is_up() {
+                            return None;
+                        }
+                        break result;
+                    }
+                    self.tick_clock();
+                }
+            };
+        self.echo_scripted(&result, &input[start..]);
+        Some(result)
+    }
+
+    // Echoes a line read from a script, as a terminal would have shown it being typed.
+    fn echo_scripted(&self, result: &io::Result<usize>, line: &str) {
+        if self.line_reader.is_scripted() && matches!(result, Ok(read) if *read > 0) {
+            self.renderer.println(line.trim_end_matches('\n'));
+        }
+    }
+
+    // Waits for Enter, or for time to run out. Closed input and time running out are noticed by
+    // the next read that expects a command.
+    fn wait_for_enter(&self) {
+        self.renderer.println("Press Enter to continue");
+        let _ = self.read_line_ticking(&mut String::new());
+    }
+
+    /// Shows why the stage was failed, returns whether the player wants to try again.
+    pub fn stage_failed_prompt(&self, reason: &str) -> bool {
+        self.render();
+        self.renderer
+            .println(&format!("{} - Stage failed!", reason));
+        self.renderer
+            .print("Press Enter to try again, or type 'q' to quit: ");
+        let mut input: String = String::new();
+        let result: io::Result<usize> = self.line_reader.read_line(&mut input);
+        self.echo_scripted(&result, &input);
+        match result {
+            Ok(0) | Err(_) => false,
+            Ok(_) => input.trim() != "q",
+        }
+    }
+
+    pub fn show_stats(&self) {
+        self.render();
+        self.renderer.println("Stats:\n");
+        self.renderer.print(&self.profile.summary());
+        self.wait_for_enter();
+    }
+
+    pub fn show_notice(&self, notice: &str) {
+        self.render();
+        self.renderer.println(notice);
+        self.wait_for_enter();
+    }
+
+    pub fn stage_complete_prompt(&mut self, is_last_stage: bool) {
+        let elapsed: Duration = self.started_at.elapsed();
+        self.profile
+            .record_completion(self.layout_hash, self.turn, elapsed);
+        let rank: Option<usize> = self.leaderboard.submit(
+            self.layout_hash,
+            &self.stage_name,
+            Score {
+                player: leaderboard::player_name(),
+                moves: self.turn,
+                time: elapsed,
+                date: leaderboard::now(),
+            },
+        );
+        self.show_completion(is_last_stage, rank);
+        // Saved once the screen is drawn, so that errors stay in view until Enter is pressed.
+        self.save_profile();
+        if let Err(error) = self.leaderboard.save() {
+            self.renderer
+                .error(&format!("Could not save scores: {}", error));
+        }
+        self.wait_for_enter();
+    }
+
+    // Shows the sorted board, the rating and the high scores, with the new score at `rank`.
+    fn show_completion(&self, is_last_stage: bool, rank: Option<usize>) {
         let game_complete_message: &str = match is_last_stage {
             true => "You Won! 🎉",
             false => "Stage complete! 💪",
         };
 
         self.render();
-        println!(
-            "All Stacks Sorted! - {}\nPress Enter to continue",
-            game_complete_message
-        );
-        self.line_reader.read_line(&mut String::new());
+        self.renderer
+            .println(&format!("All Stacks Sorted! - {}", game_complete_message));
+        if let (Some(par), Some(stars)) = (self.settings.par, self.star_rating()) {
+            let comparison: String = match self.turn {
+                turn if turn < par => format!("{} under par", par - turn),
+                turn if turn == par => "matched par".to_string(),
+                turn => format!("{} over par", turn - par),
+            };
+            self.renderer.println(&format!(
+                "{} {} turns, par {} ({})",
+                "★".repeat(stars) + &"☆".repeat(3 - stars),
+                self.turn,
+                par,
+                comparison
+            ));
+        }
+        self.renderer.println("\nHigh scores:");
+        self.renderer
+            .print(&self.leaderboard.format_table(self.layout_hash, rank));
     }
 
     pub fn show_help(&self) {
         self.render();
-        println!("Help:\n");
-        println!(
-            "Enter two numbers separated by a space to move units between stacks (e.g., '2 3')"
+        self.renderer.println("Help:\n");
+        self.renderer.println(
+            "Enter two numbers separated by a space to move units between stacks (e.g., '2 3')",
         );
-        println!("Type 'u' to undo the last move");
-        println!("Type 'r' to reset the stage");
-        println!("Type 'q' to quit the game");
-        println!("Press Enter to continue");
-        self.line_reader.read_line(&mut String::new());
+        self.renderer
+            .println(&format!("Rules: {}", self.settings.rule_set.describe()));
+        if self.settings.rule_set.allows_counts() {
+            self.renderer
+                .println("Add a count to move only some of the units (e.g., '2 3 x2')");
+        }
+        self.renderer
+            .println("Separate moves with commas to make several at once (e.g., '2 3, 1 2')");
+        self.renderer.println("Type '?' for a hint");
+        if self.settings.extra_stacks > 0 {
+            self.renderer.println(&format!(
+                "Type 'e' to add an empty stack, {} left (no three stars when used)",
+                self.extra_stacks_left()
+            ));
+        }
+        if self.settings.shuffles > 0 {
+            self.renderer.println(&format!(
+                "Type 'm' to shuffle the unsorted stacks, {} left (no three stars when used)",
+                self.shuffles_left()
+            ));
+        }
+        self.renderer.println("Type 'u' to undo the last move");
+        self.renderer.println("Type 'r' to reset the stage");
+        self.renderer.println("Type 's' to show your stats");
+        self.renderer.println("Type 'q' to quit the game");
+        self.wait_for_enter();
     }
 
-    pub fn read_valid_input(&self) -> UserInput {
+    /// Reads input until it is valid, starting with `prompt` instead of the default one if set.
+    pub fn read_valid_input(&self, prompt: Option<String>) -> UserInput {
         let mut user_input: UserInput = UserInput::new_menu_option(MenuOption::Help);
         let mut input: String = String::new();
         let default_prompt: String = "Input move or type 'h' for help".to_string();
-        let invalid_input_prompt: String = format!(
-            "Invalid input!\nPlease enter two different numbers between 1 and {} separated by a space", self.stacks.len()
-        );
         let mut current_prompt: String;
-        let mut next_prompt: String = String::new();
+        let mut next_prompt: String = prompt.unwrap_or_default();
 
         loop {
             if self.stage_complete() {
@@ -112,68 +367,190 @@ impl<TLR: LineReader + Default> Game<TLR> {
             }
 
             self.render();
-            print!("{}: ", current_prompt);
+            self.renderer.print(&format!("{}: ", current_prompt));
 
-            let str_input: &str = match no_legal_moves {
-                // TODO: show help when no legal moves, handle flushing outside.
-                _ => {
-                    io::stdout().flush().unwrap(); // Flush to ensure the message is displayed before reading input
-                    input.clear();
-                    self.line_reader.read_line(&mut input);
-                    input.trim()
+            // TODO: show help when no legal moves.
+            input.clear();
+            match self.read_line_ticking(&mut input) {
+                None => {
+                    user_input = UserInput::new_menu_option(MenuOption::TimeUp);
+                    break;
                 }
-            };
+                Some(Ok(0)) => {
+                    user_input = UserInput::new_menu_option(MenuOption::InputClosed);
+                    break;
+                }
+                Some(Err(error)) => {
+                    self.renderer
+                        .error(&format!("Could not read input: {}", error));
+                    user_input = UserInput::new_menu_option(MenuOption::InputClosed);
+                    break;
+                }
+                Some(Ok(_)) => {}
+            }
+            let str_input: &str = input.trim();
 
             user_input = match str_input {
                 "h" => UserInput::new_menu_option(MenuOption::Help),
                 "q" => UserInput::new_menu_option(MenuOption::Quit),
                 "r" => UserInput::new_menu_option(MenuOption::Reset),
-                "u" => UserInput::new_menu_option(MenuOption::Undo),
-                _ => {
-                    let parts: Vec<&str> = input.trim().split_whitespace().collect();
-                    if parts.len() != 2 {
-                        next_prompt = invalid_input_prompt.clone();
-                        continue;
-                    }
-
-                    let from = match parts[0].parse::<usize>() {
-                        Ok(num) if num - 1 < self.stacks.len() => num - 1,
-                        _ => {
-                            next_prompt = invalid_input_prompt.clone();
-                            continue;
+                "s" => UserInput::new_menu_option(MenuOption::Stats),
+                "?" => {
+                    next_prompt = match self.hint() {
+                        Some((from, to)) => format!("Hint: try '{} {}'", from + 1, to + 1),
+                        None => {
+                            "No hint available - press 'u' to undo or 'r' to restart".to_string()
                         }
                     };
-
-                    let to = match parts[1].parse::<usize>() {
-                        Ok(num) if ((num - 1 < self.stacks.len()) && (num - 1 != from)) => num - 1,
-                        _ => {
-                            next_prompt = invalid_input_prompt.clone();
+                    continue;
+                }
+                "e" if self.extra_stacks_left() == 0 => {
+                    next_prompt = "No empty stacks left to add in this stage".to_string();
+                    continue;
+                }
+                "e" => UserInput::new_menu_option(MenuOption::ExtraStack),
+                "m" if self.shuffles_left() == 0 => {
+                    next_prompt = "No shuffles left in this stage".to_string();
+                    continue;
+                }
+                "m" => UserInput::new_menu_option(MenuOption::Shuffle),
+                "u" if self.settings.undo_policy == UndoPolicy::Disabled => {
+                    next_prompt = "Undo is disabled in this stage".to_string();
+                    continue;
+                }
+                "u" => UserInput::new_menu_option(MenuOption::Undo),
+                _ => {
+                    let (moves, error) = self.plan_moves(str_input);
+                    match (moves.is_empty(), error) {
+                        (true, Some(error)) => {
+                            next_prompt = error;
                             continue;
                         }
-                    };
-
-                    if self.move_requires_more_room(from, to) {
-                        next_prompt =
-                            Game::<TLR>::illegal_move_prompt("Not enough room in the target stack");
-                        continue;
+                        (_, error) => UserInput {
+                            menu_option: MenuOption::Move,
+                            moves,
+                            error,
+                        },
                     }
+                }
+            };
+            break;
+        }
+        user_input
+    }
 
-                    if self.stack_tops_mismatch(from, to) {
-                        next_prompt = Game::<TLR>::illegal_move_prompt(
-                            "Units can only be moved towards identical units, or empty stacks",
-                        );
-                        continue;
-                    }
+    // Validates a sequence of moves such as '2 3, 1 2' on a copy of the board. Stops at the first
+    // illegal move, returning the moves before it along with the reason it was rejected.
+    fn plan_moves(&self, input: &str) -> (Vec<PlannedMove>, Option<String>) {
+        let commands: Vec<&str> = input
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+            .collect();
+        let mut board: Vec<Stack> = self.stacks.clone();
+        let mut moves: Vec<PlannedMove> = Vec::new();
+        if commands.is_empty() {
+            return (moves, Some(self.invalid_input_prompt()));
+        }
 
-                    UserInput {
-                        menu_option: MenuOption::Move,
-                        stack_move: Some((from, to)),
-                    }
+        for (ind, command) in commands.iter().enumerate() {
+            let planned: PlannedMove = match self.check_move(&board, command) {
+                Ok(planned) => planned,
+                Err(error) if commands.len() == 1 => return (moves, Some(error)),
+                Err(error) => {
+                    let prefix: String =
+                        format!("Move {} of {} ('{}')", ind + 1, commands.len(), command);
+                    return (moves, Some(format!("{} - {}", prefix, error)));
                 }
             };
-            break;
+            let quantity: usize = planned.quantity.unwrap_or_else(|| {
+                self.settings
+                    .rule_set
+                    .movable_quantity(&board, planned.from, planned.to)
+            });
+            let kind: Kind = board[planned.from].pop_residents_with_limit(Some(quantity));
+            board[planned.to].push_immigrants(kind);
+            moves.push(planned);
         }
-        return user_input;
+        (moves, None)
+    }
+
+    fn invalid_input_prompt(&self) -> String {
+        format!(
+            "Invalid input!\nPlease enter two different numbers between 1 and {} separated by a space",
+            self.stacks.len()
+        )
+    }
+
+    // Checks a single move such as '2 3' or '2 3 x2' against the board.
+    fn check_move(&self, board: &[Stack], command: &str) -> Result<PlannedMove, String> {
+        let rule_set: RuleSet = self.settings.rule_set;
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(self.invalid_input_prompt());
+        }
+        let stack_index = |part: &str| match part.parse::<usize>() {
+            Ok(num) if (1..=board.len()).contains(&num) => Some(num - 1),
+            _ => None,
+        };
+        let (Some(from), Some(to)) = (stack_index(parts[0]), stack_index(parts[1])) else {
+            return Err(self.invalid_input_prompt());
+        };
+        if from == to {
+            return Err(self.invalid_input_prompt());
+        }
+
+        if let Some(prompt) = self.constraint_prompt(board, from, to) {
+            return Err(Game::<TLR, TRD>::illegal_move_prompt(&prompt));
+        }
+        if rule_set.move_requires_more_room(board, from, to) {
+            return Err(Game::<TLR, TRD>::illegal_move_prompt(
+                "Not enough room in the target stack",
+            ));
+        }
+        if rule_set.stack_tops_mismatch(board, from, to) {
+            return Err(Game::<TLR, TRD>::illegal_move_prompt(
+                rule_set.mismatch_prompt(),
+            ));
+        }
+
+        // An optional third part such as 'x2' picks how many units to move.
+        let movable: usize = rule_set.movable_quantity(board, from, to);
+        let quantity: Option<usize> = match parts.get(2) {
+            None => None,
+            Some(_) if !rule_set.allows_counts() => {
+                return Err(Game::<TLR, TRD>::illegal_move_prompt(
+                    "Choosing how many units to move isn't allowed in this stage",
+                ));
+            }
+            Some(part) => match part.strip_prefix('x').map(str::parse::<usize>) {
+                Some(Ok(quantity)) if (1..=movable).contains(&quantity) => Some(quantity),
+                _ => {
+                    return Err(Game::<TLR, TRD>::illegal_move_prompt(&format!(
+                        "Between 1 and {} units can be moved, e.g. '{} {} x1'",
+                        movable,
+                        from + 1,
+                        to + 1
+                    )));
+                }
+            },
+        };
+        Ok(PlannedMove { from, to, quantity })
+    }
+
+    // Explains which stack constraint forbids the move, if any.
+    fn constraint_prompt(&self, stacks: &[Stack], from: usize, to: usize) -> Option<String> {
+        let prompt: String = match constraint_violated(stacks, from, to)? {
+            Constraint::Locked(kind_id) => format!(
+                "Stack {} is locked until {} is sorted",
+                from + 1,
+                self.kind_label(kind_id)
+            ),
+            Constraint::Capped(kind_id) => {
+                format!("Stack {} only accepts {}", to + 1, self.kind_label(kind_id))
+            }
+        };
+        Some(prompt)
     }
 
     fn illegal_move_prompt(prompt: &str) -> String {
@@ -181,7 +558,6 @@ impl<TLR: LineReader + Default> Game<TLR> {
     }
 }
 
-#[allow(dead_code)]
 const COLORS: [[u8; 3]; 11] = [
     [255, 0, 0],
     [0, 255, 0],
@@ -202,3 +578,114 @@ const COLORS: [[u8; 3]; 11] = [
     [255, 255, 255],
     // [0, 0, 0],
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::difficulty::Difficulty;
+    use crate::line_reader::MockLineReader;
+    use crate::renderer::BufferRenderer;
+    use std::cell::Cell;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    type SnapshotGame = Game<MockLineReader, BufferRenderer>;
+
+    // Compares against `src/game/snapshots/<name>.txt`, run with UPDATE_SNAPSHOTS=1 to rewrite it.
+    fn assert_snapshot(name: &str, output: &str) {
+        let path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/game/snapshots")
+            .join(format!("{}.txt", name));
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, output).unwrap();
+            return;
+        }
+        let expected: String = fs::read_to_string(&path)
+            .unwrap_or_else(|error| panic!("Could not read {}: {}", path.display(), error));
+        assert!(
+            output == expected,
+            "Output differs from {}\n--- expected\n{}\n--- got\n{}",
+            path.display(),
+            expected,
+            output
+        );
+    }
+
+    // Difficulties are set by hand rather than by the solver, so that snapshots only depend on
+    // how stages are drawn.
+    fn stages() -> Vec<SnapshotGame> {
+        let no_input = MockLineReader {
+            index: Cell::new(0),
+            lines: Vec::new(),
+        };
+        let mut stages: Vec<SnapshotGame> = Game::get_stages(no_input);
+        let difficulties = [Difficulty::Easy, Difficulty::Hard, Difficulty::Hard];
+        for (stage, difficulty) in stages.iter_mut().zip(difficulties) {
+            stage.difficulty = Some(difficulty);
+        }
+        stages
+    }
+
+    // Shows each screen without colour, then with it, and checks both against their snapshots.
+    fn assert_screens(name: &str, stage: &mut SnapshotGame, show: impl Fn(&SnapshotGame)) {
+        for color in [false, true] {
+            let renderer: BufferRenderer = BufferRenderer::new(color);
+            stage.set_renderer(renderer.clone());
+            show(stage);
+            let suffix: &str = if color { "_color" } else { "" };
+            assert_snapshot(&format!("{}{}", name, suffix), &renderer.take());
+        }
+    }
+
+    #[test]
+    fn test_stage_snapshots() {
+        for (ind, mut stage) in stages().into_iter().enumerate() {
+            assert_screens(&format!("stage_{}", ind + 1), &mut stage, Game::render);
+        }
+    }
+
+    #[test]
+    fn test_glyphs_are_padded_by_columns() {
+        let mut stage: SnapshotGame =
+            Game::new_from_vecs(vec![vec![1, 2, 3, 0]], None, MockLineReader::default());
+        for (kind_id, glyph) in [(1, "猫"), (2, "e\u{301}"), (3, "R")] {
+            let metadata: KindMetadata = KindMetadata {
+                name: kind_id.to_string(),
+                glyph: Some(glyph.to_string()),
+                ..KindMetadata::default()
+            };
+            stage.settings.kinds.insert(kind_id, metadata);
+        }
+        let renderer: BufferRenderer = BufferRenderer::default();
+        stage.set_renderer(renderer.clone());
+        stage.render();
+
+        assert!(
+            renderer.take().contains(" 1: 猫  e\u{301}  R __ \n"),
+            "Every unit takes three columns"
+        );
+    }
+
+    #[test]
+    fn test_help_snapshots() {
+        let mut stage: SnapshotGame = stages().swap_remove(0);
+        stage.settings.extra_stacks = 1;
+        stage.settings.shuffles = 1;
+        assert_screens("help", &mut stage, Game::show_help);
+    }
+
+    #[test]
+    fn test_completion_snapshots() {
+        let mut stage: SnapshotGame = stages().swap_remove(0);
+        stage.settings.par = Some(3);
+        while !stage.stage_complete() {
+            let (from, to) = stage.hint().unwrap();
+            stage.move_legally(from, to);
+        }
+        assert_screens("complete", &mut stage, |stage| {
+            stage.show_completion(false, None);
+        });
+    }
+}
@@ -0,0 +1,141 @@
+use crate::game::rules::RuleSet;
+use crate::game::solver::{Solver, StackMove};
+use crate::game::stack::Stack;
+use crate::game::{Game, StageOutcome};
+use crate::line_reader::MockLineReader;
+use crate::renderer::BufferRenderer;
+use std::cell::Cell;
+
+// Stages are played headless, their screens are collected instead of printed.
+pub type HarnessGame = Game<MockLineReader, BufferRenderer>;
+
+// Any solution drives a stage to the end, so the search settles for a greedy one early.
+const SOLUTION_NODE_LIMIT: usize = 1_000;
+
+/// How a driven stage ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ending {
+    Completed,
+    /// Input ran out on a board where no legal move changes anything.
+    Stuck,
+    /// Input ran out while moves were still possible.
+    OutOfInput,
+    /// The player quit, or declined to retry a failed stage.
+    Quit,
+}
+
+/// What is left of a stage once its commands have been played.
+pub struct Run {
+    pub ending: Ending,
+    pub stacks: Vec<Stack>,
+    pub turns: usize,
+    pub commands_read: usize,
+}
+
+/// Plays `stage` with `commands` as the player's input, one per line. The stage ends once the
+/// commands run out at the latest, so runs always terminate.
+pub fn drive(mut stage: HarnessGame, commands: &[&str]) -> Run {
+    stage.line_reader = MockLineReader {
+        index: Cell::new(0),
+        lines: commands.iter().map(|command| command.to_string()).collect(),
+    };
+    stage.set_renderer(BufferRenderer::default());
+    let rule_set: RuleSet = stage.settings.rule_set;
+    let ending: Ending = match stage.turn_loop() {
+        StageOutcome::Completed => Ending::Completed,
+        StageOutcome::Quit => Ending::Quit,
+        StageOutcome::InputClosed if rule_set.legal_moves(&stage.stacks).is_empty() => {
+            Ending::Stuck
+        }
+        StageOutcome::InputClosed => Ending::OutOfInput,
+    };
+    Run {
+        ending,
+        commands_read: stage.line_reader.index.get(),
+        stacks: stage.stacks,
+        turns: stage.turn,
+    }
+}
+
+/// A solution for the stage, not always the shortest, written as the player would type them.
+pub fn solution_commands(stage: &HarnessGame) -> Vec<String> {
+    let solver: Solver = Solver::new(stage.units_per_kind.clone(), stage.settings.rule_set)
+        .with_node_limit(SOLUTION_NODE_LIMIT);
+    let moves: Vec<StackMove> = match solver.search(&stage.stacks).0 {
+        Some(solution) => solution.moves,
+        None => panic!("{} has no solution", stage.stage_name),
+    };
+    moves
+        .iter()
+        .map(|(from, to)| format!("{} {}", from + 1, to + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(vecs: Vec<Vec<usize>>) -> HarnessGame {
+        Game::new_from_vecs(vecs, None, MockLineReader::default())
+    }
+
+    fn drive_solution(stage: HarnessGame) -> Run {
+        let commands: Vec<String> = solution_commands(&stage);
+        drive(
+            stage,
+            &commands.iter().map(String::as_str).collect::<Vec<&str>>(),
+        )
+    }
+
+    #[test]
+    fn test_every_stage_is_completed_by_its_solution() {
+        for stage in Game::get_stages(MockLineReader::default()) {
+            let moves: usize = solution_commands(&stage).len();
+            let run: Run = drive_solution(stage);
+
+            assert_eq!(run.ending, Ending::Completed);
+            assert_eq!(run.commands_read, moves);
+            assert_eq!(run.turns, moves, "The completing move takes no turn");
+        }
+    }
+
+    #[test]
+    fn test_every_rule_set_is_completed_by_its_solution() {
+        for rule_set in RuleSet::ALL {
+            let mut rules_stage: HarnessGame =
+                stage(vec![vec![2, 1, 0], vec![1, 2, 0], vec![0, 0, 0]]);
+            rules_stage.settings.rule_set = rule_set;
+            let run: Run = drive_solution(rules_stage);
+
+            assert_eq!(run.ending, Ending::Completed, "{}", rule_set);
+        }
+    }
+
+    #[test]
+    fn test_running_out_of_input() {
+        let run: Run = drive(
+            stage(vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]]),
+            &["2 3", "9 9", "1 2"],
+        );
+
+        assert_eq!(run.ending, Ending::OutOfInput);
+        assert_eq!(run.commands_read, 3, "Invalid commands are read too");
+        assert_eq!(run.turns, 3);
+        let board: Vec<Stack> = stage(vec![vec![2, 0, 0], vec![1, 1], vec![2, 2]]).stacks;
+        assert_eq!(run.stacks, board);
+    }
+
+    #[test]
+    fn test_stuck_and_quit() {
+        let stuck: Run = drive(stage(vec![vec![1, 2], vec![2, 1]]), &["1 2"]);
+        assert_eq!(stuck.ending, Ending::Stuck);
+        assert_eq!(stuck.turns, 1);
+
+        let quit: Run = drive(
+            stage(vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]]),
+            &["q", "2 3"],
+        );
+        assert_eq!(quit.ending, Ending::Quit);
+        assert_eq!(quit.commands_read, 1);
+    }
+}
@@ -0,0 +1,550 @@
+use crate::game::rules::RuleSet;
+use crate::game::settings::{KindMetadata, UndoPolicy};
+use crate::game::stack::constraint::Constraint;
+use crate::game::stack::kind::{IsEmpty, IsJoker, Kind, KindId};
+use crate::game::stack::Stack;
+use crate::game::Game;
+use crate::line_reader::LineReader;
+use crate::renderer::{display_width, Renderer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+// Level packs are plain text, one stage per section:
+//
+//     # Comments start with '#'
+//     [Stage - 1]
+//     par = 3
+//     time = 90
+//     moves = 5
+//     undo = free
+//     rules = fill
+//     fog = true
+//     extra = 1
+//     extra_capacity = 2
+//     shuffles = 1
+//     kind = red color=ff0000 glyph=R
+//     kind = blue
+//     stack = blue red 0
+//     stack = red blue
+//     stack = blue 0 capped=blue
+//
+// Declared kinds get the ids 1, 2, ... in order, and can be referred to by name or id.
+// Stacks list their units from the bottom up, with 0 marking an empty slot and '*' a joker.
+// Empty slots can only come after the units.
+// A trailing `locked=K` keeps units in the stack until kind K is sorted,
+// and `capped=K` makes the stack accept kind K only.
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+    NoStages,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "Could not read level file: {}", error),
+            LevelError::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            LevelError::NoStages => write!(f, "The level file does not contain any stages"),
+        }
+    }
+}
+
+impl From<io::Error> for LevelError {
+    fn from(error: io::Error) -> Self {
+        LevelError::Io(error)
+    }
+}
+
+fn syntax_error(line: usize, message: &str) -> LevelError {
+    LevelError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn parse_number(line: usize, value: &str) -> Result<usize, LevelError> {
+    value
+        .parse::<usize>()
+        .map_err(|_| syntax_error(line, &format!("'{}' is not a number", value)))
+}
+
+fn parse_bool(line: usize, value: &str) -> Result<bool, LevelError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(syntax_error(line, "Expected 'true' or 'false'")),
+    }
+}
+
+// Units are written as kind ids, names of declared kinds, or '*' for jokers.
+fn parse_unit(
+    line: usize,
+    value: &str,
+    kinds: &BTreeMap<KindId, KindMetadata>,
+) -> Result<KindId, LevelError> {
+    if value == "*" {
+        return Ok(Kind::get_joker_id());
+    }
+    if let Some((kind_id, _)) = kinds.iter().find(|(_, metadata)| metadata.name == value) {
+        return Ok(*kind_id);
+    }
+    value
+        .parse::<KindId>()
+        .map_err(|_| syntax_error(line, &format!("Unknown kind '{}'", value)))
+}
+
+// Parses a hex colour such as 'ff8800' into red, green and blue, '#' starts a comment.
+fn parse_color(line: usize, hex: &str) -> Result<[u8; 3], LevelError> {
+    let channel = |range: std::ops::Range<usize>| {
+        hex.get(range)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+    };
+    match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+        (6, Some(red), Some(green), Some(blue)) => Ok([red, green, blue]),
+        _ => Err(syntax_error(
+            line,
+            &format!("'{}' is not a colour such as 'ff8800'", hex),
+        )),
+    }
+}
+
+fn parse_kind(line: usize, value: &str) -> Result<KindMetadata, LevelError> {
+    let mut parts = value.split_whitespace();
+    let name: &str = parts
+        .next()
+        .ok_or_else(|| syntax_error(line, "Expected a kind name"))?;
+    if name == "*" || name.parse::<usize>().is_ok() {
+        return Err(syntax_error(
+            line,
+            &format!("'{}' can't be used as a kind name", name),
+        ));
+    }
+    let mut metadata: KindMetadata = KindMetadata {
+        name: name.to_string(),
+        ..KindMetadata::default()
+    };
+    for option in parts {
+        match option.split_once('=') {
+            Some(("color", color)) => metadata.color = Some(parse_color(line, color)?),
+            Some(("glyph", glyph)) if display_width(glyph) > 2 => {
+                let message: String = format!("Glyph '{}' is wider than two columns", glyph);
+                return Err(syntax_error(line, &message));
+            }
+            Some(("glyph", glyph)) => metadata.glyph = Some(glyph.to_string()),
+            _ => {
+                let message: String = format!("Unknown kind option '{}'", option);
+                return Err(syntax_error(line, &message));
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
+    pub fn load_stages(path: &Path, line_reader: TLR) -> Result<Vec<Game<TLR, TRD>>, LevelError> {
+        Game::parse_stages(&fs::read_to_string(path)?, line_reader)
+    }
+
+    pub fn parse_stages(text: &str, line_reader: TLR) -> Result<Vec<Game<TLR, TRD>>, LevelError> {
+        let mut stages: Vec<Game<TLR, TRD>> = Vec::new();
+        let mut stage: Option<Game<TLR, TRD>> = None;
+        let mut header_line: usize = 0;
+        let mut constrained: Vec<(usize, usize)> = Vec::new(); // Line and index of each stack.
+
+        for (ind, raw_line) in text.lines().enumerate() {
+            let line_number: usize = ind + 1;
+            let line: &str = raw_line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                if let Some(finished) = &stage {
+                    Game::check_stage(finished, header_line, &constrained)?;
+                }
+                header_line = line_number;
+                constrained.clear();
+                stages.extend(stage.take());
+                let name: String = line[1..line.len() - 1].trim().to_string();
+                stage = Some(Game::new(Vec::new(), Some(name), line_reader.clone()));
+                continue;
+            }
+
+            let current: &mut Game<TLR, TRD> = stage
+                .as_mut()
+                .ok_or_else(|| syntax_error(line_number, "Expected a '[stage name]' header"))?;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| syntax_error(line_number, "Expected 'key = value'"))?;
+            let value: &str = value.trim();
+            match key.trim() {
+                "par" => current.settings.par = Some(parse_number(line_number, value)?),
+                "time" => {
+                    let seconds: u64 = parse_number(line_number, value)? as u64;
+                    current.settings.time_limit = Some(Duration::from_secs(seconds));
+                }
+                "rules" => {
+                    current.settings.rule_set = value
+                        .parse::<RuleSet>()
+                        .map_err(|message| syntax_error(line_number, &message))?
+                }
+                "fog" => current.settings.fog = parse_bool(line_number, value)?,
+                "extra" => current.settings.extra_stacks = parse_number(line_number, value)?,
+                "extra_capacity" => {
+                    current.settings.extra_stack_capacity = Some(parse_number(line_number, value)?)
+                }
+                "shuffles" => current.settings.shuffles = parse_number(line_number, value)?,
+                "moves" => current.settings.move_limit = Some(parse_number(line_number, value)?),
+                "undo" => {
+                    current.settings.undo_policy = match value {
+                        "costs-move" => UndoPolicy::CostsMove,
+                        "free" => UndoPolicy::Free,
+                        "disabled" => UndoPolicy::Disabled,
+                        _ => {
+                            return Err(syntax_error(
+                                line_number,
+                                "Undo must be one of 'costs-move', 'free' or 'disabled'",
+                            ))
+                        }
+                    }
+                }
+                "kind" => {
+                    let metadata: KindMetadata = parse_kind(line_number, value)?;
+                    let kinds: &mut BTreeMap<KindId, KindMetadata> = &mut current.settings.kinds;
+                    if kinds.values().any(|kind| kind.name == metadata.name) {
+                        let message: String = format!("Kind '{}' is declared twice", metadata.name);
+                        return Err(syntax_error(line_number, &message));
+                    }
+                    kinds.insert(kinds.len() + 1, metadata);
+                }
+                "stack" => {
+                    let mut vec: Vec<usize> = Vec::new();
+                    let mut constraint: Option<Constraint> = None;
+                    for unit in value.split_whitespace() {
+                        let kinds: &BTreeMap<KindId, KindMetadata> = &current.settings.kinds;
+                        let parsed: Constraint = match unit.split_once('=') {
+                            None => {
+                                vec.push(parse_unit(line_number, unit, kinds)?);
+                                continue;
+                            }
+                            Some(("locked", kind)) => {
+                                Constraint::Locked(parse_unit(line_number, kind, kinds)?)
+                            }
+                            Some(("capped", kind)) => {
+                                Constraint::Capped(parse_unit(line_number, kind, kinds)?)
+                            }
+                            Some((option, _)) => {
+                                let message: String = format!("Unknown stack option '{}'", option);
+                                return Err(syntax_error(line_number, &message));
+                            }
+                        };
+                        if constraint.replace(parsed).is_some() {
+                            let message: &str = "A stack can have only one constraint";
+                            return Err(syntax_error(line_number, message));
+                        }
+                    }
+                    if let Some(Constraint::Capped(kind_id)) = constraint {
+                        if vec
+                            .iter()
+                            .any(|id| !id.is_empty() && *id != kind_id && !id.is_joker())
+                        {
+                            let message: String = format!(
+                                "A stack capped to {} can't hold other kinds",
+                                current.kind_label(kind_id)
+                            );
+                            return Err(syntax_error(line_number, &message));
+                        }
+                    }
+                    let mut stack: Stack = Stack::new_from_vec(vec)
+                        .map_err(|error| syntax_error(line_number, &error.to_string()))?;
+                    if constraint.is_some() {
+                        constrained.push((line_number, current.stacks.len()));
+                    }
+                    stack.set_constraint(constraint);
+                    current.stacks.push(stack);
+                    current.hidden.push(0);
+                }
+                key => return Err(syntax_error(line_number, &format!("Unknown key '{}'", key))),
+            }
+        }
+        if let Some(finished) = &stage {
+            Game::check_stage(finished, header_line, &constrained)?;
+        }
+        stages.extend(stage);
+        if stages.is_empty() {
+            return Err(LevelError::NoStages);
+        }
+
+        // Stages are created before their stacks are known, rebuild them to count the kinds.
+        Ok(stages.iter().map(|stage| stage.clone()).collect())
+    }
+
+    // A stage without units would be complete before the first move. Constraints can only name
+    // kinds that have units in the stage, or the stack could never be unlocked or filled.
+    fn check_stage(
+        stage: &Game<TLR, TRD>,
+        header_line: usize,
+        constrained: &[(usize, usize)],
+    ) -> Result<(), LevelError> {
+        if stage.stacks.iter().all(Stack::is_vacant) {
+            return Err(syntax_error(header_line, "A stage needs at least one unit"));
+        }
+        for (line_number, stack_ind) in constrained {
+            let (Some(Constraint::Locked(kind_id)) | Some(Constraint::Capped(kind_id))) =
+                stage.stacks[*stack_ind].get_constraint()
+            else {
+                continue;
+            };
+            let units: usize = stage
+                .stacks
+                .iter()
+                .map(|stack| stack.count_units(kind_id))
+                .sum();
+            if units == 0 {
+                let message: String = format!(
+                    "Kind {} has no units in this stage",
+                    stage.kind_label(kind_id)
+                );
+                return Err(syntax_error(*line_number, &message));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::harness::{self, Ending, HarnessGame, Run};
+    use crate::line_reader::MockLineReader;
+
+    const PACK: &str = "
+        # A two stage pack
+        [First]
+        par = 3
+        time = 90
+        stack = 2 1 0
+        stack = 1 2
+        stack = 2 0
+
+        [Second]
+        moves = 5
+        undo = disabled
+        rules = fill
+        fog = true
+        extra = 2
+        extra_capacity = 4
+        shuffles = 1
+        stack = 1 1 * 0 # inline comment
+        stack = 1 0 0 locked=1
+        stack = 0 0 capped=1
+
+        [Named]
+        kind = red color=ff0000 glyph=R
+        kind = blue
+        stack = blue red 0
+        stack = red blue
+        stack = 2 0 capped=blue
+    ";
+
+    #[test]
+    fn test_parse_stages() {
+        let stages: Vec<Game<MockLineReader>> =
+            Game::parse_stages(PACK, MockLineReader::default()).unwrap();
+
+        assert_eq!(stages.len(), 3);
+        assert_eq!(stages[0].stage_name, "First");
+        assert_eq!(stages[0].settings.par, Some(3));
+        assert_eq!(stages[0].stacks.len(), 3);
+        assert_eq!(stages[0].settings.time_limit, Some(Duration::from_secs(90)));
+        assert_eq!(stages[1].settings.par, None);
+        assert_eq!(stages[1].settings.time_limit, None);
+        assert_eq!(stages[1].settings.move_limit, Some(5));
+        assert_eq!(stages[1].settings.undo_policy, UndoPolicy::Disabled);
+        assert_eq!(stages[0].settings.rule_set, RuleSet::Classic);
+        assert_eq!(stages[1].settings.rule_set, RuleSet::FillAsMuchAsFits);
+        assert!(!stages[0].settings.fog);
+        assert!(stages[1].settings.fog);
+        assert_eq!(stages[0].settings.extra_stacks, 0);
+        assert_eq!(stages[1].settings.extra_stacks, 2);
+        assert_eq!(stages[1].settings.extra_stack_capacity, Some(4));
+        assert_eq!(stages[1].settings.shuffles, 1);
+        assert_eq!(stages[1].get_total_quantity(1), 3);
+        assert_eq!(stages[1].get_total_quantity(Kind::get_joker_id()), 1);
+        assert_eq!(stages[0].stacks[0].get_constraint(), None);
+        assert_eq!(
+            stages[1].stacks[1].get_constraint(),
+            Some(Constraint::Locked(1))
+        );
+        assert_eq!(
+            stages[1].stacks[2].get_constraint(),
+            Some(Constraint::Capped(1))
+        );
+
+        let named: &Game<MockLineReader> = &stages[2];
+        assert_eq!(named.stacks[0].iter_unit_ids().collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(
+            named.stacks[2].get_constraint(),
+            Some(Constraint::Capped(2))
+        );
+        assert_eq!(named.settings.kinds[&1].color, Some([255, 0, 0]));
+        assert_eq!(named.settings.kinds[&1].glyph.as_deref(), Some("R"));
+        assert_eq!(named.kind_label(2), "blue");
+        assert_eq!(stages[0].kind_label(2), "2");
+    }
+
+    #[test]
+    fn test_loaded_stages_can_be_played() {
+        for fog in ["false", "true"] {
+            let pack: String = format!(
+                "[S]\nfog = {}\nstack = 2 1 0\nstack = 1 2\nstack = 2 0",
+                fog
+            );
+            let mut stages: Vec<HarnessGame> =
+                Game::parse_stages(&pack, MockLineReader::default()).unwrap();
+            let stage: HarnessGame = stages.remove(0);
+            let commands: Vec<String> = harness::solution_commands(&stage);
+            let commands: Vec<&str> = commands.iter().map(String::as_str).collect();
+            let run: Run = harness::drive(stage, &commands);
+
+            assert_eq!(run.ending, Ending::Completed, "fog = {}", fog);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let missing_header =
+            Game::<MockLineReader>::parse_stages("stack = 1 0", MockLineReader::default());
+        assert!(matches!(
+            missing_header,
+            Err(LevelError::Syntax { line: 1, .. })
+        ));
+
+        let bad_unit =
+            Game::<MockLineReader>::parse_stages("[A]\nstack = 1 x", MockLineReader::default());
+        assert!(matches!(bad_unit, Err(LevelError::Syntax { line: 2, .. })));
+
+        let empty =
+            Game::<MockLineReader>::parse_stages("# nothing here", MockLineReader::default());
+        assert!(matches!(empty, Err(LevelError::NoStages)));
+
+        let bad_policy = Game::<MockLineReader>::parse_stages(
+            "[A]\nundo = sometimes",
+            MockLineReader::default(),
+        );
+        assert!(matches!(
+            bad_policy,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+
+        let bad_rules =
+            Game::<MockLineReader>::parse_stages("[A]\nrules = chaos", MockLineReader::default());
+        assert!(matches!(bad_rules, Err(LevelError::Syntax { line: 2, .. })));
+
+        let bad_option = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 0 frozen=1",
+            MockLineReader::default(),
+        );
+        assert!(matches!(
+            bad_option,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+
+        let two_constraints = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 0 locked=1 capped=1",
+            MockLineReader::default(),
+        );
+        assert!(matches!(
+            two_constraints,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+
+        let unknown_kind =
+            Game::<MockLineReader>::parse_stages("[A]\nstack = red 0", MockLineReader::default());
+        assert!(matches!(
+            unknown_kind,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+
+        let bad_color = Game::<MockLineReader>::parse_stages(
+            "[A]\nkind = red color=red",
+            MockLineReader::default(),
+        );
+        assert!(matches!(bad_color, Err(LevelError::Syntax { line: 2, .. })));
+
+        let twice = Game::<MockLineReader>::parse_stages(
+            "[A]\nkind = red\nkind = red",
+            MockLineReader::default(),
+        );
+        assert!(matches!(twice, Err(LevelError::Syntax { line: 3, .. })));
+
+        let bad_fog =
+            Game::<MockLineReader>::parse_stages("[A]\nfog = yes", MockLineReader::default());
+        assert!(matches!(bad_fog, Err(LevelError::Syntax { line: 2, .. })));
+
+        let no_units = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 0\n\n[E]\nstack = 0 0",
+            MockLineReader::default(),
+        );
+        assert!(matches!(no_units, Err(LevelError::Syntax { line: 4, .. })));
+
+        let no_stacks = Game::<MockLineReader>::parse_stages("[A]\n[B]", MockLineReader::default());
+        assert!(matches!(no_stacks, Err(LevelError::Syntax { line: 1, .. })));
+
+        let absent_lock = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 0 locked=2\nstack = 1 0",
+            MockLineReader::default(),
+        );
+        assert!(matches!(
+            absent_lock,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+
+        let absent_cap = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 1\nstack = 0 0 capped=3",
+            MockLineReader::default(),
+        );
+        assert!(matches!(
+            absent_cap,
+            Err(LevelError::Syntax { line: 3, .. })
+        ));
+
+        let mixed_cap = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 2 0 capped=1\nstack = 2 1 0",
+            MockLineReader::default(),
+        );
+        assert!(matches!(mixed_cap, Err(LevelError::Syntax { line: 2, .. })));
+
+        let joker_cap = Game::<MockLineReader>::parse_stages(
+            "[A]\nstack = 1 * 0 capped=1\nstack = 1 0 0",
+            MockLineReader::default(),
+        );
+        assert!(joker_cap.is_ok(), "Jokers may share a capped stack");
+
+        let wide_glyph = Game::<MockLineReader>::parse_stages(
+            "[A]\nkind = red glyph=RED\nstack = 1 0",
+            MockLineReader::default(),
+        );
+        assert!(matches!(
+            wide_glyph,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+
+        let gap =
+            Game::<MockLineReader>::parse_stages("[A]\nstack = 1 0 1", MockLineReader::default());
+        assert!(matches!(gap, Err(LevelError::Syntax { line: 2, .. })));
+
+        let unknown_key =
+            Game::<MockLineReader>::parse_stages("[A]\ncolour = red", MockLineReader::default());
+        assert!(matches!(
+            unknown_key,
+            Err(LevelError::Syntax { line: 2, .. })
+        ));
+    }
+}
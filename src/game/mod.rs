@@ -1,49 +1,109 @@
+mod canonical;
+mod difficulty;
 mod entry;
+mod fog;
 mod gui;
+#[cfg(test)]
+mod harness;
+mod level;
+mod random;
+mod rules;
+mod settings;
+mod shuffle;
+mod solver;
 mod stack;
 mod stages;
 
 use crate::line_reader::LineReader;
+use crate::profile::leaderboard::Leaderboard;
+use crate::profile::Profile;
+use crate::renderer::{Renderer, StdoutRenderer};
+pub use difficulty::{Difficulty, DifficultyReport};
 use entry::Entry;
-use stack::kind::{HasId, IsEmpty, Kind, KindId};
+pub use level::LevelError;
+use random::Random;
+pub use rules::RuleSet;
+use settings::{StageSettings, UndoPolicy};
+use stack::kind::{HasId, IsJoker, Kind, KindId};
 use stack::Stack;
 use std::collections::HashMap;
+use std::time::Instant;
+
+/// How a stage was left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StageOutcome {
+    Completed,
+    Quit,
+    /// Input ended or failed before the stage was completed.
+    InputClosed,
+}
 
-pub struct Game<TR: LineReader> {
+pub struct Game<TR: LineReader, TRD: Renderer = StdoutRenderer> {
     stacks: Vec<Stack>,
     units_per_kind: HashMap<KindId, usize>,
     kind_indices: HashMap<KindId, usize>,
-    kinds_status: usize,
+    kinds_status: Vec<bool>, // Whether each kind is complete, by kind index.
+    hidden: Vec<usize>,      // Units at the bottom of each stack that fog hasn't exposed yet.
     turn: usize,
     stage_name: String,
+    layout_hash: u64,
+    difficulty: Option<Difficulty>,
+    settings: StageSettings,
     ledger: Vec<Entry>,
+    assisted: bool, // Whether a power-up was used during the attempt.
+    random: Random,
+    started_at: Instant,
+    profile: Profile,
+    leaderboard: Leaderboard,
     line_reader: TR,
+    renderer: TRD,
 }
 
-impl<TLR: LineReader + Default + Clone> Game<TLR> {
-    fn new(stacks: Vec<Stack>, stage_name: Option<String>, line_reader: TLR) -> Game<TLR> {
-        let units_per_kind: HashMap<KindId, usize> = Game::<TLR>::count_kinds(&stacks);
-        let kind_indices: HashMap<KindId, usize> = Game::<TLR>::index_kinds(&units_per_kind);
-        Game {
+impl<TLR: LineReader + Default + Clone, TRD: Renderer> Game<TLR, TRD> {
+    fn new(stacks: Vec<Stack>, stage_name: Option<String>, line_reader: TLR) -> Game<TLR, TRD> {
+        let units_per_kind: HashMap<KindId, usize> = Game::<TLR, TRD>::count_kinds(&stacks);
+        let kind_indices: HashMap<KindId, usize> = Game::<TLR, TRD>::index_kinds(&units_per_kind);
+        let layout_hash: u64 = canonical::canonical_hash(&stacks);
+        let kinds_status: Vec<bool> = vec![false; units_per_kind.len()];
+        let hidden: Vec<usize> = vec![0; stacks.len()];
+        let mut game: Game<TLR, TRD> = Game {
             stacks,
             units_per_kind,
             kind_indices,
-            kinds_status: 0,
+            kinds_status,
+            hidden,
             turn: 1,
             stage_name: stage_name.unwrap_or("".to_string()),
+            layout_hash,
+            difficulty: None,
+            settings: StageSettings::default(),
             ledger: Vec::new(),
+            assisted: false,
+            random: Random::from_time(),
+            started_at: Instant::now(),
+            profile: Profile::default(),
+            leaderboard: Leaderboard::default(),
             line_reader,
-        }
+            renderer: TRD::default(),
+        };
+        game.refresh_kind_status(); // Stages may start partly sorted.
+        game
     }
 
-    fn clone(&self) -> Game<TLR> {
-        Game::new(
-            self.stacks.iter().map(|stack| stack.clone()).collect(),
+    fn clone(&self) -> Game<TLR, TRD> {
+        let mut game: Game<TLR, TRD> = Game::new(
+            self.stacks.clone(),
             Some(self.stage_name.clone()),
             self.line_reader.clone(),
-        )
+        );
+        game.difficulty = self.difficulty;
+        game.settings = self.settings.clone();
+        game.hidden = self.hidden.clone();
+        game.renderer = self.renderer.clone();
+        game
     }
 
+    // Jokers are counted like a kind of their own, so that they get their own completion status.
     fn count_kinds(stacks: &[Stack]) -> HashMap<KindId, usize> {
         let mut units_per_kind: HashMap<KindId, usize> = HashMap::new(); // Initialize the HashMap
         for stack in stacks {
@@ -65,24 +125,19 @@ impl<TLR: LineReader + Default + Clone> Game<TLR> {
     }
 
     fn move_is_illegal(&self, from: usize, to: usize) -> bool {
-        (from == to) || self.move_requires_more_room(from, to) || self.stack_tops_mismatch(from, to)
+        self.settings
+            .rule_set
+            .move_is_illegal(&self.stacks, from, to)
     }
 
     fn move_is_legal(&self, from: usize, to: usize) -> bool {
         !self.move_is_illegal(from, to)
     }
 
-    fn move_requires_more_room(&self, from: usize, to: usize) -> bool {
-        self.stacks[to].get_vacancy() < self.stacks[from].get_top_unit_quantity()
-    }
-
-    fn stack_tops_mismatch(&self, from: usize, to: usize) -> bool {
-        let immigrant_id: KindId = self.stacks[from].get_top_unit_id();
-        let resident_id: KindId = self.stacks[to].get_top_unit_id();
-
-        let tops_match: bool =
-            (immigrant_id == resident_id) || immigrant_id.is_empty() || resident_id.is_empty();
-        !tops_match
+    fn movable_quantity(&self, from: usize, to: usize) -> usize {
+        self.settings
+            .rule_set
+            .movable_quantity(&self.stacks, from, to)
     }
 
     fn no_legal_moves(&self) -> bool {
@@ -97,16 +152,35 @@ impl<TLR: LineReader + Default + Clone> Game<TLR> {
     }
 
     fn update_kind_status(&mut self, stack_ind: usize) {
-        let resident_id: KindId = self.stacks[stack_ind].get_top_unit_id();
-        if resident_id.is_empty() {
-            return;
+        let joker_id: KindId = Kind::get_joker_id();
+        let mut resident_ids: Vec<KindId> = self.stacks[stack_ind].iter_unit_ids().collect();
+        resident_ids.dedup();
+        for resident_id in resident_ids {
+            if resident_id.is_joker() {
+                continue;
+            }
+            let resident_index: usize = self.get_kind_index(resident_id);
+            self.kinds_status[resident_index] = self.stacks[stack_ind]
+                .holds_whole_kind(resident_id, self.get_total_quantity(resident_id));
         }
-        let resident_quantity: usize = self.stacks[stack_ind].get_top_unit_quantity();
 
-        let resident_bit: usize = 1 << self.get_kind_index(resident_id);
-        self.kinds_status |= resident_bit; // Initially set the resident bit to 1.
-        if resident_quantity != self.get_total_quantity(resident_id) {
-            self.kinds_status -= resident_bit; // zero the resident bit.
+        // Jokers are complete once every one of them sits in a finished stack.
+        if self.units_per_kind.contains_key(&joker_id) {
+            let joker_index: usize = self.get_kind_index(joker_id);
+            let jokers_settled: bool = self.stacks.iter().all(|stack| {
+                stack.count_units(joker_id) == 0
+                    || stack.get_sole_kind_id().is_some_and(|kind_id| {
+                        stack.holds_whole_kind(kind_id, self.get_total_quantity(kind_id))
+                    })
+            });
+            self.kinds_status[joker_index] = jokers_settled;
+        }
+    }
+
+    // Recomputes the status of every kind from the whole board.
+    fn refresh_kind_status(&mut self) {
+        for stack_ind in 0..self.stacks.len() {
+            self.update_kind_status(stack_ind);
         }
     }
 
@@ -119,7 +193,7 @@ impl<TLR: LineReader + Default + Clone> Game<TLR> {
     }
 
     fn ledge(&mut self, from: usize, to: usize, kind: Kind, quantity: usize) {
-        self.ledger.push(Entry {
+        self.ledger.push(Entry::Move {
             from,
             to,
             _kind: kind,
@@ -133,67 +207,246 @@ impl<TLR: LineReader + Default + Clone> Game<TLR> {
         self.turn += if self.stage_complete() { 0 } else { 1 };
     }
 
-    fn move_units(&mut self, from: usize, to: usize, limit_: Option<usize>) {
+    fn move_units(&mut self, from: usize, to: usize, limit_: Option<usize>) -> Kind {
         let kind: Kind = self.stacks[from].pop_residents_with_limit(limit_);
-        let quantity: usize = kind.get_quantity();
         self.stacks[to].push_immigrants(kind);
+        self.reveal_top(from);
 
         self.update_state(from, to);
-        match limit_ {
-            Some(_) => {} // Limits are specified in undo moves, Undo moves should not be ledged.
-            _ => self.ledge(from, to, kind, quantity),
-        };
+        kind
     }
 
     fn move_legally(&mut self, from: usize, to: usize) {
-        self.move_units(from, to, None);
+        self.move_quantity_legally(from, to, self.movable_quantity(from, to));
     }
 
+    fn move_quantity_legally(&mut self, from: usize, to: usize, quantity: usize) {
+        let kind: Kind = self.move_units(from, to, Some(quantity));
+        self.ledge(from, to, kind, quantity);
+    }
+
+    // Undo moves should not be ledged.
     fn move_forcefully(&mut self, from: usize, to: usize, quantity: usize) {
         self.move_units(from, to, Some(quantity));
     }
 
     fn stage_complete(&self) -> bool {
-        self.kinds_status == (1 << self.units_per_kind.len()) - 1
+        self.kinds_status.iter().all(|complete| *complete)
     }
 
     fn undo_move(&mut self) {
-        match self.ledger.last() {
-            Some(entry) => {
-                let (from, to, quantity) = (entry.to, entry.from, entry.quantity);
-                self.ledger.pop();
-                self.move_forcefully(from, to, quantity);
+        // No moves to undo when the ledger is empty.
+        match self.ledger.pop() {
+            Some(Entry::Move {
+                from, to, quantity, ..
+            }) => {
+                let turn: usize = self.turn;
+                self.move_forcefully(to, from, quantity);
+                if self.settings.undo_policy == UndoPolicy::Free {
+                    self.turn = turn.saturating_sub(1).max(1);
+                }
+            }
+            // Later moves were undone first, so the added stack is empty again.
+            Some(Entry::ExtraStack) => {
+                self.stacks.pop();
+                self.hidden.pop();
             }
-            _ => {} // No moves to undo.
+            Some(Entry::Shuffle { previous, hidden }) => {
+                self.stacks = previous;
+                self.hidden = hidden;
+                self.refresh_kind_status();
+            }
+            None => {}
+        }
+    }
+
+    fn extra_stacks_left(&self) -> usize {
+        let added: usize = self
+            .ledger
+            .iter()
+            .filter(|entry| matches!(entry, Entry::ExtraStack))
+            .count();
+        self.settings.extra_stacks.saturating_sub(added)
+    }
+
+    // Appends an empty stack, without taking a turn.
+    fn add_extra_stack(&mut self) {
+        let capacity: usize = self.settings.extra_stack_capacity.unwrap_or_else(|| {
+            self.stacks
+                .iter()
+                .map(Stack::get_capacity)
+                .max()
+                .unwrap_or(0)
+        });
+        self.stacks.push(Stack::new(capacity, 0, Vec::new()));
+        self.hidden.push(0);
+        self.ledger.push(Entry::ExtraStack);
+        self.assisted = true;
+    }
+
+    // Moves still allowed in a move-limited stage, the turn counter starts at 1.
+    fn moves_left(&self) -> Option<usize> {
+        self.settings
+            .move_limit
+            .map(|limit| (limit + 1).saturating_sub(self.turn))
+    }
+
+    fn out_of_moves(&self) -> bool {
+        !self.stage_complete() && self.moves_left() == Some(0)
+    }
+
+    // Restores the board of the backup, leaving the clock and the profile untouched.
+    fn reset_stage(&mut self, stage_backup: &Game<TLR, TRD>) {
+        self.stacks = stage_backup.stacks.clone();
+        self.hidden = stage_backup.hidden.clone();
+        self.refresh_kind_status();
+        self.turn = stage_backup.turn;
+        self.ledger = stage_backup.ledger.clone();
+        self.assisted = stage_backup.assisted;
+    }
+
+    fn time_is_up(&self) -> bool {
+        match self.settings.time_limit {
+            Some(limit) => self.started_at.elapsed() >= limit,
+            None => false,
+        }
+    }
+
+    /// Shows a clock counting up in the status line, unless the stage already has a time limit.
+    pub fn enable_clock(&mut self) {
+        self.settings.clock = true;
+    }
+
+    /// Sends the stage's output to `renderer` instead of the default one.
+    pub fn set_renderer(&mut self, renderer: TRD) {
+        self.renderer = renderer;
+    }
+
+    fn save_profile(&self) {
+        if let Err(error) = self.profile.save() {
+            self.renderer
+                .error(&format!("Could not save profile: {}", error));
         }
     }
 
-    fn turn_loop(&mut self) {
-        let stage_backup: Game<TLR> = self.clone();
+    fn start_attempt(&mut self) {
+        self.started_at = Instant::now();
+        self.profile
+            .record_attempt(self.layout_hash, &self.stage_name);
+    }
+
+    // Lets the player retry the stage from the start, returns false if they'd rather quit.
+    fn fail_stage(&mut self, reason: &str, stage_backup: &Game<TLR, TRD>) -> bool {
+        if !self.stage_failed_prompt(reason) {
+            return false;
+        }
+        self.reset_stage(stage_backup);
+        self.start_attempt();
+        true
+    }
+
+    fn turn_loop(&mut self) -> StageOutcome {
+        if self.settings.fog {
+            self.conceal_stacks();
+        }
+        let stage_backup: Game<TLR, TRD> = self.clone();
+        self.start_attempt();
+        let mut prompt: Option<String> = None;
         loop {
             if self.stage_complete() {
-                break;
+                return StageOutcome::Completed;
             }
-            let user_input: gui::UserInput = self.read_valid_input();
-            match user_input.stack_move {
-                Some((from, to)) => self.move_legally(from, to),
-                _ => match user_input.menu_option {
-                    gui::MenuOption::Help => self.show_help(),
-                    gui::MenuOption::Quit => std::process::exit(0),
-                    gui::MenuOption::Reset => *self = stage_backup.clone(),
-                    gui::MenuOption::Undo => self.undo_move(),
-                    _ => {}
+            if self.out_of_moves() && !self.fail_stage("Out of moves!", &stage_backup) {
+                return StageOutcome::Quit;
+            }
+            let user_input: gui::UserInput = self.read_valid_input(prompt.take());
+            match user_input.menu_option {
+                gui::MenuOption::Move => {
+                    for planned in user_input.moves {
+                        // The rest of a sequence is dropped once the stage is over.
+                        if self.stage_complete() || self.out_of_moves() {
+                            break;
+                        }
+                        match planned.quantity {
+                            Some(quantity) => {
+                                self.move_quantity_legally(planned.from, planned.to, quantity)
+                            }
+                            None => self.move_legally(planned.from, planned.to),
+                        }
+                    }
+                    prompt = user_input.error;
+                }
+                gui::MenuOption::Help => self.show_help(),
+                gui::MenuOption::Quit => return StageOutcome::Quit,
+                gui::MenuOption::InputClosed => return StageOutcome::InputClosed,
+                gui::MenuOption::Reset => {
+                    self.profile.record_reset(self.layout_hash);
+                    self.reset_stage(&stage_backup);
+                }
+                gui::MenuOption::Stats => self.show_stats(),
+                gui::MenuOption::TimeUp => {
+                    if !self.fail_stage("Time's up!", &stage_backup) {
+                        return StageOutcome::Quit;
+                    }
+                }
+                gui::MenuOption::ExtraStack => self.add_extra_stack(),
+                gui::MenuOption::Shuffle => match self.shuffle_stacks() {
+                    true => {}
+                    false => self.show_notice("No solvable shuffle was found, try again"),
                 },
+                gui::MenuOption::Undo => {
+                    self.profile.record_undo(self.layout_hash);
+                    self.undo_move();
+                }
             }
         }
     }
 
+    /// Three stars for matching par, two for staying within half again as many turns.
+    /// Using a power-up caps the rating at two stars.
+    fn star_rating(&self) -> Option<usize> {
+        self.settings.par.map(|par| match self.turn {
+            turn if turn <= par && !self.assisted => 3,
+            turn if 2 * turn <= 3 * par => 2,
+            _ => 1,
+        })
+    }
+
+    // Rates the stage unless sorting its pack already did. The search runs before the attempt
+    // starts, so it doesn't count against the clock.
+    fn prepare(&mut self) {
+        if self.difficulty.is_none() {
+            self.rate();
+        }
+    }
+
     pub fn play(line_reader: TLR) {
-        let stages: Vec<Game<TLR>> = Game::get_stages(line_reader);
+        Game::<TLR, TRD>::play_stages(Game::get_stages(line_reader));
+    }
+
+    pub fn play_stages(stages: Vec<Game<TLR, TRD>>) {
+        // Progress that can't be loaded is reported, and kept in memory from then on.
+        let renderer: TRD = stages[0].renderer.clone();
+        let mut profile: Profile = Profile::load_default().unwrap_or_else(|error| {
+            renderer.error(&format!("Could not load profile: {}", error));
+            Profile::default()
+        });
+        let mut leaderboard: Leaderboard = Leaderboard::load_default().unwrap_or_else(|error| {
+            renderer.error(&format!("Could not load scores: {}", error));
+            Leaderboard::default()
+        });
         let last_stage_index: usize = stages.len() - 1;
         for (ind, mut stage) in stages.into_iter().enumerate() {
-            stage.turn_loop();
+            stage.profile = profile;
+            stage.leaderboard = leaderboard;
+            stage.prepare();
+            if stage.turn_loop() != StageOutcome::Completed {
+                stage.save_profile();
+                return;
+            }
             stage.stage_complete_prompt(ind == last_stage_index);
+            profile = stage.profile;
+            leaderboard = stage.leaderboard;
         }
     }
 }
@@ -201,8 +454,10 @@ impl<TLR: LineReader + Default + Clone> Game<TLR> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::line_reader::MockLineReader;
+    use crate::line_reader::{MockLineReader, ScriptReader};
+    use crate::renderer::BufferRenderer;
     use std::cell::Cell;
+    use std::time::Duration;
 
     #[test]
     fn test_get_stages() {
@@ -221,7 +476,271 @@ mod tests {
             lines: vec!["2 3".to_string(), "1 2".to_string(), "3 1".to_string()],
         };
         let mut first_stage: Game<MockLineReader> = Game::get_stages(mock_reader)[0].clone();
-        // not the best way to test since we can get stuck in a loop, but its a start.
-        first_stage.turn_loop();
+        assert_eq!(first_stage.turn_loop(), StageOutcome::Completed);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let mut stage: Game<MockLineReader> =
+            Game::get_stages(MockLineReader::default())[0].clone();
+        stage.settings.time_limit = Some(Duration::from_secs(90));
+        assert!(!stage.time_is_up());
+        assert!(stage
+            .status_line()
+            .starts_with("Turn - 1    Time left - 1:"));
+
+        stage.settings.time_limit = Some(Duration::ZERO);
+        assert!(stage.time_is_up());
+        stage.show_help();
+        assert_eq!(
+            stage.line_reader.index.get(),
+            0,
+            "Info screens close once time is up"
+        );
+        assert!(matches!(
+            stage.read_valid_input(None).menu_option,
+            gui::MenuOption::TimeUp
+        ));
+    }
+
+    #[test]
+    fn test_move_limit() {
+        let mut stage: Game<MockLineReader> =
+            Game::get_stages(MockLineReader::default())[0].clone();
+        stage.settings.move_limit = Some(2);
+        assert_eq!(stage.moves_left(), Some(2));
+        assert!(stage.status_line().ends_with("Moves left - 2"));
+
+        stage.move_legally(1, 2);
+        stage.move_legally(0, 1);
+        assert_eq!(stage.moves_left(), Some(0));
+        assert!(stage.out_of_moves());
+
+        stage.settings.undo_policy = UndoPolicy::Free;
+        stage.undo_move();
+        assert_eq!(stage.moves_left(), Some(1));
+
+        stage.move_legally(0, 1);
+        stage.settings.undo_policy = UndoPolicy::CostsMove;
+        stage.undo_move();
+        assert!(stage.out_of_moves());
+    }
+
+    #[test]
+    fn test_undo_disabled() {
+        let mock_reader = MockLineReader {
+            index: Cell::new(0),
+            lines: vec!["u".to_string(), "h".to_string()],
+        };
+        let mut stage: Game<MockLineReader> = Game::get_stages(mock_reader)[0].clone();
+        stage.settings.undo_policy = UndoPolicy::Disabled;
+        assert!(matches!(
+            stage.read_valid_input(None).menu_option,
+            gui::MenuOption::Help
+        ));
+    }
+
+    #[test]
+    fn test_move_sequences() {
+        let mock_reader = MockLineReader {
+            index: Cell::new(0),
+            lines: vec!["2 3, 1 2; 3 1,".to_string()],
+        };
+        let mut first_stage: Game<MockLineReader> = Game::get_stages(mock_reader)[0].clone();
+        assert_eq!(first_stage.turn_loop(), StageOutcome::Completed);
+        assert_eq!(first_stage.turn, 3);
+
+        let mock_reader = MockLineReader {
+            index: Cell::new(0),
+            lines: vec!["0 1".to_string(), "2 3, 2 3, 1 2".to_string()],
+        };
+        let stage: Game<MockLineReader> = Game::get_stages(mock_reader)[0].clone();
+        let user_input: gui::UserInput = stage.read_valid_input(None);
+        assert_eq!(
+            user_input.moves.len(),
+            1,
+            "Stack 0 is rejected, then the first move is kept"
+        );
+        let error: String = user_input.error.unwrap();
+        assert!(error.starts_with("Move 2 of 3 ('2 3')"), "{}", error);
+        assert!(error.contains("Not enough room"), "{}", error);
+    }
+
+    #[test]
+    fn test_script_ends_the_game() {
+        let script: ScriptReader = ScriptReader::new("2 3\n");
+        let mut stage: Game<ScriptReader, BufferRenderer> = Game::get_stages(script)[0].clone();
+        let renderer: BufferRenderer = BufferRenderer::default();
+        stage.set_renderer(renderer.clone());
+
+        assert!(matches!(
+            stage.read_valid_input(None).menu_option,
+            gui::MenuOption::Move
+        ));
+        assert!(
+            renderer.take().ends_with("2 3\n"),
+            "Scripted commands are echoed"
+        );
+        assert!(matches!(
+            stage.read_valid_input(None).menu_option,
+            gui::MenuOption::InputClosed
+        ));
+    }
+
+    #[test]
+    fn test_running_out_of_input_ends_the_stage() {
+        // The default mock makes two moves, which don't complete the first stage.
+        let mut stage: Game<MockLineReader> =
+            Game::get_stages(MockLineReader::default())[0].clone();
+        assert_eq!(stage.turn_loop(), StageOutcome::InputClosed);
+        assert_eq!(stage.turn, 3);
+    }
+
+    #[test]
+    fn test_output_goes_to_the_renderer() {
+        let mut stage: Game<MockLineReader, BufferRenderer> = Game::new_from_vecs(
+            vec![vec![1, 2, 0], vec![2, 1, 0]],
+            None,
+            MockLineReader::default(),
+        );
+        let renderer: BufferRenderer = BufferRenderer::new(false);
+        stage.set_renderer(renderer.clone());
+        stage.render();
+        let plain: String = renderer.take();
+        assert!(
+            plain.contains(" 1:  1  2 __ \n 2:  2  1 __ \n"),
+            "{}",
+            plain
+        );
+        assert!(!plain.contains('\x1b'));
+
+        stage.set_renderer(BufferRenderer::new(true));
+        stage.render();
+        assert!(stage
+            .renderer
+            .take()
+            .contains("\x1b[38;2;255;0;0m 1\x1b[0m "));
+    }
+
+    #[test]
+    fn test_partial_moves() {
+        let mock_reader = MockLineReader {
+            index: Cell::new(0),
+            lines: vec!["1 2 x3".to_string(), "1 2 x2".to_string()],
+        };
+        let mut stage: Game<MockLineReader> =
+            Game::new_from_vecs(vec![vec![1, 1, 1, 0], vec![1, 0, 0]], None, mock_reader);
+        assert!(stage.move_is_illegal(0, 1));
+
+        stage.settings.rule_set = RuleSet::FillAsMuchAsFits;
+        assert!(stage.move_is_legal(0, 1));
+        assert_eq!(stage.movable_quantity(0, 1), 2);
+
+        let user_input: gui::UserInput = stage.read_valid_input(None);
+        assert_eq!(
+            user_input.moves,
+            [gui::PlannedMove {
+                from: 0,
+                to: 1,
+                quantity: Some(2)
+            }]
+        );
+
+        stage.move_quantity_legally(0, 1, 1);
+        assert_eq!(stage.stacks[1].get_occupancy(), 2);
+        stage.undo_move();
+        assert_eq!(stage.stacks[0].get_top_unit_quantity(), 3);
+    }
+
+    #[test]
+    fn test_jokers_complete_inside_finished_stacks() {
+        let joker: KindId = Kind::get_joker_id();
+        let mut stage: Game<MockLineReader> = Game::new_from_vecs(
+            vec![vec![1, joker, 0], vec![1, 0, 0], vec![2, 2, 0]],
+            None,
+            MockLineReader::default(),
+        );
+        assert!(stage.move_is_legal(0, 2), "Jokers go onto any kind");
+
+        stage.move_legally(0, 2);
+        assert!(!stage.stage_complete());
+        stage.move_legally(1, 0);
+        assert!(stage.stage_complete());
+    }
+
+    #[test]
+    fn test_extra_stack() {
+        let mut stage: Game<MockLineReader> =
+            Game::get_stages(MockLineReader::default())[0].clone();
+        stage.settings.par = Some(3);
+        assert_eq!(
+            stage.extra_stacks_left(),
+            0,
+            "Only level files grant extra stacks"
+        );
+        stage.settings.extra_stacks = 1;
+        assert_eq!(stage.extra_stacks_left(), 1);
+
+        stage.add_extra_stack();
+        assert_eq!(stage.stacks.len(), 4);
+        assert_eq!(stage.stacks[3].get_capacity(), 3);
+        assert_eq!(stage.extra_stacks_left(), 0);
+        stage.move_legally(0, 3);
+        assert_eq!(stage.turn, 2, "Adding a stack doesn't take a turn");
+        assert_eq!(stage.star_rating(), Some(2));
+
+        stage.undo_move();
+        stage.undo_move();
+        assert_eq!(stage.stacks.len(), 3);
+        assert_eq!(stage.extra_stacks_left(), 1);
+    }
+
+    #[test]
+    fn test_completion_is_computed_from_the_whole_board() {
+        let mut partly_sorted: Game<MockLineReader> = Game::new_from_vecs(
+            vec![vec![1, 1, 0], vec![2, 3, 0], vec![3, 2, 0], vec![0, 0]],
+            None,
+            MockLineReader::default(),
+        );
+        assert_eq!(partly_sorted.kinds_status, [true, false, false]);
+        assert!(!partly_sorted.stage_complete());
+
+        let backup: Game<MockLineReader> = partly_sorted.clone();
+        partly_sorted.move_legally(0, 3);
+        partly_sorted.reset_stage(&backup);
+        assert_eq!(partly_sorted.kinds_status, [true, false, false]);
+
+        let sorted: Game<MockLineReader> = Game::new_from_vecs(
+            vec![vec![1, 1, 0], vec![2, 0]],
+            None,
+            MockLineReader::default(),
+        );
+        assert!(sorted.stage_complete());
+    }
+
+    #[test]
+    fn test_many_kinds() {
+        // More kinds than bits in a word, the last one split over two stacks.
+        let mut vecs: Vec<Vec<usize>> = (1..=70).map(|kind_id| vec![kind_id, 0]).collect();
+        vecs.push(vec![70, 0]);
+        let mut stage: Game<MockLineReader> =
+            Game::new_from_vecs(vecs, None, MockLineReader::default());
+        assert!(!stage.stage_complete());
+
+        stage.move_legally(70, 69);
+        assert!(stage.stage_complete());
+    }
+
+    #[test]
+    fn test_star_rating() {
+        let mut stage: Game<MockLineReader> =
+            Game::get_stages(MockLineReader::default())[0].clone();
+        assert_eq!(stage.star_rating(), None);
+
+        stage.settings.par = Some(4);
+        for (turn, stars) in [(3, 3), (4, 3), (5, 2), (6, 2), (7, 1)] {
+            stage.turn = turn;
+            assert_eq!(stage.star_rating(), Some(stars));
+        }
     }
 }
@@ -0,0 +1,61 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Replaces a zero seed, which would keep xorshift stuck at zero.
+const FALLBACK_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Xorshift64* generator, enough for shuffling boards without pulling in a crate.
+#[derive(Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            state: if seed == 0 { FALLBACK_SEED } else { seed },
+        }
+    }
+
+    /// Seeds the generator from the system clock.
+    pub fn from_time() -> Random {
+        let nanos: u128 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        Random::new(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..bound`, `bound` must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            items.swap(index, self.below(index + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shuffle_is_a_permutation() {
+        let mut random: Random = Random::new(0);
+        let mut items: Vec<usize> = (0..20).collect();
+        random.shuffle(&mut items);
+
+        assert_ne!(items, (0..20).collect::<Vec<usize>>());
+        items.sort();
+        assert_eq!(items, (0..20).collect::<Vec<usize>>());
+    }
+}
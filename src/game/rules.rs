@@ -0,0 +1,308 @@
+use crate::game::stack::constraint::Constraint;
+use crate::game::stack::kind::{IsEmpty, IsJoker, KindId};
+use crate::game::stack::Stack;
+use std::fmt;
+use std::str::FromStr;
+
+// Move rules are kept free of `Game` so that search code can apply them to bare boards.
+
+/// Whether every unit of the kind sits in a single stack holding nothing else but jokers.
+pub fn kind_is_complete(stacks: &[Stack], kind_id: KindId) -> bool {
+    let total: usize = stacks.iter().map(|stack| stack.count_units(kind_id)).sum();
+    stacks
+        .iter()
+        .any(|stack| stack.holds_whole_kind(kind_id, total))
+}
+
+// Jokers match any kind, in either direction.
+fn kinds_match(immigrant_id: KindId, resident_id: KindId) -> bool {
+    immigrant_id == resident_id || immigrant_id.is_joker() || resident_id.is_joker()
+}
+
+/// The constraint that forbids the move, whatever the rule set: the source's lock until its kind
+/// is complete, or the target's cap, which only lets in its kind and jokers.
+pub fn constraint_violated(stacks: &[Stack], from: usize, to: usize) -> Option<Constraint> {
+    match stacks[from].get_constraint() {
+        Some(Constraint::Locked(kind_id)) if !kind_is_complete(stacks, kind_id) => {
+            return Some(Constraint::Locked(kind_id));
+        }
+        _ => {}
+    }
+    let immigrant_id: KindId = stacks[from].get_top_unit_id();
+    match stacks[to].get_constraint() {
+        Some(Constraint::Capped(kind_id))
+            if immigrant_id != kind_id && !immigrant_id.is_joker() =>
+        {
+            Some(Constraint::Capped(kind_id))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum RuleSet {
+    /// Whole runs move onto identical units or empty stacks, and must fit entirely.
+    #[default]
+    Classic,
+    /// A single unit moves at a time.
+    OneAtATime,
+    /// As many units of the run as fit are moved, or a count chosen by the player.
+    FillAsMuchAsFits,
+    /// Empty stacks only take a single unit of any kind, runs move whole onto identical units.
+    AnyOntoEmpty,
+    /// Units can only be added to empty stacks, or to stacks holding nothing but their kind.
+    BottomUp,
+}
+
+impl RuleSet {
+    pub const ALL: [RuleSet; 5] = [
+        RuleSet::Classic,
+        RuleSet::OneAtATime,
+        RuleSet::FillAsMuchAsFits,
+        RuleSet::AnyOntoEmpty,
+        RuleSet::BottomUp,
+    ];
+
+    pub fn move_is_illegal(&self, stacks: &[Stack], from: usize, to: usize) -> bool {
+        (from == to)
+            || constraint_violated(stacks, from, to).is_some()
+            || self.move_requires_more_room(stacks, from, to)
+            || self.stack_tops_mismatch(stacks, from, to)
+    }
+
+    pub fn move_is_legal(&self, stacks: &[Stack], from: usize, to: usize) -> bool {
+        !self.move_is_illegal(stacks, from, to)
+    }
+
+    pub fn move_requires_more_room(&self, stacks: &[Stack], from: usize, to: usize) -> bool {
+        let vacancy: usize = stacks[to].get_vacancy();
+        match self {
+            RuleSet::Classic | RuleSet::BottomUp => vacancy < stacks[from].get_top_unit_quantity(),
+            RuleSet::OneAtATime | RuleSet::FillAsMuchAsFits => vacancy == 0,
+            RuleSet::AnyOntoEmpty => vacancy < self.movable_quantity(stacks, from, to).max(1),
+        }
+    }
+
+    pub fn stack_tops_mismatch(&self, stacks: &[Stack], from: usize, to: usize) -> bool {
+        let immigrant_id: KindId = stacks[from].get_top_unit_id();
+        let resident_id: KindId = stacks[to].get_top_unit_id();
+
+        // Moving from an empty stack is allowed, it changes nothing but costs a turn.
+        let tops_match: bool = immigrant_id.is_empty()
+            || match self {
+                RuleSet::BottomUp => stacks[to]
+                    .iter_unit_ids()
+                    .all(|unit_id| kinds_match(immigrant_id, unit_id)),
+                _ => stacks[to].is_vacant() || kinds_match(immigrant_id, resident_id),
+            };
+        !tops_match
+    }
+
+    /// Number of units a legal move carries when the player doesn't ask for a specific count.
+    pub fn movable_quantity(&self, stacks: &[Stack], from: usize, to: usize) -> usize {
+        let run: usize = stacks[from].get_top_unit_quantity();
+        match self {
+            RuleSet::Classic | RuleSet::BottomUp => run,
+            RuleSet::OneAtATime => run.min(1),
+            RuleSet::FillAsMuchAsFits => run.min(stacks[to].get_vacancy()),
+            RuleSet::AnyOntoEmpty if stacks[to].is_vacant() => run.min(1),
+            RuleSet::AnyOntoEmpty => run,
+        }
+    }
+
+    /// Whether the player may pick how many units to move, as in '2 3 x2'.
+    pub fn allows_counts(&self) -> bool {
+        *self == RuleSet::FillAsMuchAsFits
+    }
+
+    /// Legal moves that change the board, which leaves out moves from empty stacks.
+    pub fn legal_moves(&self, stacks: &[Stack]) -> Vec<(usize, usize)> {
+        let mut moves: Vec<(usize, usize)> = Vec::new();
+        for from in (0..stacks.len()).filter(|&from| !stacks[from].is_vacant()) {
+            for to in 0..stacks.len() {
+                if self.move_is_legal(stacks, from, to) {
+                    moves.push((from, to));
+                }
+            }
+        }
+        moves
+    }
+
+    pub fn describe(&self) -> &str {
+        match self {
+            RuleSet::Classic => "Whole runs move onto identical units or empty stacks",
+            RuleSet::OneAtATime => "Units move one at a time",
+            RuleSet::FillAsMuchAsFits => "As many units as fit are moved",
+            RuleSet::AnyOntoEmpty => "Empty stacks take a single unit at a time",
+            RuleSet::BottomUp => "Stacks are filled bottom-up with identical units",
+        }
+    }
+
+    pub fn mismatch_prompt(&self) -> &str {
+        match self {
+            RuleSet::BottomUp => {
+                "Units can only be moved onto empty stacks, or stacks holding only identical units"
+            }
+            _ => "Units can only be moved towards identical units, or empty stacks",
+        }
+    }
+}
+
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name: &str = match self {
+            RuleSet::Classic => "classic",
+            RuleSet::OneAtATime => "one-at-a-time",
+            RuleSet::FillAsMuchAsFits => "fill",
+            RuleSet::AnyOntoEmpty => "any-onto-empty",
+            RuleSet::BottomUp => "bottom-up",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for RuleSet {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        RuleSet::ALL
+            .into_iter()
+            .find(|rule_set| rule_set.to_string() == name)
+            .ok_or_else(|| {
+                let names: Vec<String> = RuleSet::ALL.iter().map(RuleSet::to_string).collect();
+                format!("Rules must be one of '{}'", names.join("', '"))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::stack::kind::Kind;
+
+    #[test]
+    fn test_rule_sets() {
+        // A run of three, a stack with room for two on top of a different kind, and an empty stack.
+        let board: Vec<Stack> =
+            Stack::board_from_vecs(vec![vec![2, 1, 1, 1, 0], vec![2, 1, 0, 0], vec![0, 0, 0]]);
+        let expectations: [(RuleSet, bool, usize, usize); 5] = [
+            (RuleSet::Classic, false, 3, 3),
+            (RuleSet::OneAtATime, true, 1, 1),
+            (RuleSet::FillAsMuchAsFits, true, 2, 3),
+            (RuleSet::AnyOntoEmpty, false, 3, 1),
+            (RuleSet::BottomUp, false, 3, 3),
+        ];
+        for (rule_set, onto_run, onto_run_quantity, onto_empty_quantity) in expectations {
+            assert_eq!(
+                rule_set.move_is_legal(&board, 0, 1),
+                onto_run,
+                "{}",
+                rule_set
+            );
+            assert_eq!(rule_set.movable_quantity(&board, 0, 1), onto_run_quantity);
+            assert!(rule_set.move_is_legal(&board, 0, 2), "{}", rule_set);
+            assert_eq!(rule_set.movable_quantity(&board, 0, 2), onto_empty_quantity);
+        }
+    }
+
+    #[test]
+    fn test_bottom_up_requires_identical_stacks() {
+        let board: Vec<Stack> =
+            Stack::board_from_vecs(vec![vec![1, 0], vec![2, 1, 0], vec![1, 0, 0]]);
+
+        assert!(RuleSet::Classic.move_is_legal(&board, 0, 1));
+        assert!(RuleSet::BottomUp.move_is_illegal(&board, 0, 1));
+        assert!(RuleSet::BottomUp.move_is_legal(&board, 0, 2));
+    }
+
+    #[test]
+    fn test_moves_from_empty_stacks_are_legal_no_ops() {
+        let board: Vec<Stack> = Stack::board_from_vecs(vec![vec![0, 0], vec![1, 0], vec![2, 0]]);
+
+        for rule_set in RuleSet::ALL {
+            assert!(rule_set.move_is_legal(&board, 0, 1), "{}", rule_set);
+            assert_eq!(rule_set.movable_quantity(&board, 0, 2), 0);
+            assert!(rule_set
+                .legal_moves(&board)
+                .iter()
+                .all(|&(from, _)| from != 0));
+        }
+    }
+
+    #[test]
+    fn test_jokers_match_any_kind() {
+        let joker: KindId = Kind::get_joker_id();
+        let board: Vec<Stack> =
+            Stack::board_from_vecs(vec![vec![1, joker, 0], vec![2, 0, 0], vec![2, 1, 0]]);
+
+        assert!(RuleSet::Classic.move_is_legal(&board, 0, 1));
+        assert!(
+            RuleSet::Classic.move_is_legal(&board, 1, 0),
+            "Any kind goes onto jokers"
+        );
+        assert!(RuleSet::Classic.move_is_legal(&board, 0, 2));
+        assert!(RuleSet::BottomUp.move_is_legal(&board, 0, 1));
+        assert!(RuleSet::BottomUp.move_is_illegal(&board, 1, 0));
+        assert!(
+            RuleSet::BottomUp.move_is_legal(&board, 0, 2),
+            "Jokers match every unit"
+        );
+    }
+
+    #[test]
+    fn test_constraints() {
+        let mut board: Vec<Stack> = Stack::board_from_vecs(vec![
+            vec![2, 1, 0],
+            vec![0, 0, 0],
+            vec![2, 0, 0],
+            vec![1, 0, 0],
+            vec![Kind::get_joker_id(), 0, 0],
+        ]);
+        board[0].set_constraint(Some(Constraint::Locked(2)));
+        board[1].set_constraint(Some(Constraint::Capped(1)));
+
+        assert!(
+            RuleSet::Classic.move_is_illegal(&board, 0, 1),
+            "Locked until 2 is sorted"
+        );
+        assert!(
+            RuleSet::Classic.move_is_illegal(&board, 2, 1),
+            "Only 1 enters the capped stack"
+        );
+        assert!(RuleSet::Classic.move_is_legal(&board, 3, 1));
+        assert!(
+            RuleSet::Classic.move_is_legal(&board, 4, 1),
+            "Jokers enter capped stacks too"
+        );
+        assert_eq!(
+            constraint_violated(&board, 0, 1),
+            Some(Constraint::Locked(2)),
+            "The lock is reported before the cap"
+        );
+        assert_eq!(
+            constraint_violated(&board, 2, 1),
+            Some(Constraint::Capped(1))
+        );
+        assert_eq!(constraint_violated(&board, 3, 1), None);
+        assert!(
+            RuleSet::Classic.move_is_legal(&board, 3, 0),
+            "Locked stacks still take units"
+        );
+
+        let mut unlocked: Vec<Stack> =
+            Stack::board_from_vecs(vec![vec![2, 2, 0], vec![1, 0, 0], vec![1, 0, 0]]);
+        unlocked[1].set_constraint(Some(Constraint::Locked(2)));
+        assert!(kind_is_complete(&unlocked, 2));
+        assert!(RuleSet::Classic.move_is_legal(&unlocked, 1, 2));
+        unlocked[1].set_constraint(Some(Constraint::Locked(1)));
+        assert!(RuleSet::Classic.move_is_illegal(&unlocked, 1, 2));
+    }
+
+    #[test]
+    fn test_parse_rule_sets() {
+        for rule_set in RuleSet::ALL {
+            assert_eq!(rule_set.to_string().parse::<RuleSet>(), Ok(rule_set));
+        }
+        assert!("chaos".parse::<RuleSet>().is_err());
+    }
+}
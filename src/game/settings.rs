@@ -0,0 +1,43 @@
+use crate::game::rules::RuleSet;
+use crate::game::stack::kind::KindId;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// What undoing a move costs. Applies to every stage, and matters most when moves are limited.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum UndoPolicy {
+    /// Undoing takes a turn, like any other move.
+    #[default]
+    CostsMove,
+    /// Undoing gives back the turn of the undone move.
+    Free,
+    Disabled,
+}
+
+/// How a kind is presented, kinds without metadata show their id in a palette colour.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct KindMetadata {
+    pub name: String,
+    pub color: Option<[u8; 3]>,
+    pub glyph: Option<String>,
+}
+
+/// Per-stage options, authored in level files or filled in before a stage is played.
+#[derive(Clone, Default)]
+pub struct StageSettings {
+    pub par: Option<usize>,
+    pub rule_set: RuleSet,
+    pub fog: bool,
+    pub clock: bool,
+    pub time_limit: Option<Duration>,
+    pub move_limit: Option<usize>,
+    pub undo_policy: UndoPolicy,
+    /// Empty stacks the player may add when stuck, using one rules out three stars.
+    pub extra_stacks: usize,
+    /// Capacity of added stacks, the largest capacity on the board when unset.
+    pub extra_stack_capacity: Option<usize>,
+    /// Shuffles of the unsorted stacks the player may ask for, using one rules out three stars.
+    pub shuffles: usize,
+    /// Kinds named by the stage, the n-th declared kind has id n.
+    pub kinds: BTreeMap<KindId, KindMetadata>,
+}
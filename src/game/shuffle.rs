@@ -0,0 +1,136 @@
+use crate::game::entry::Entry;
+use crate::game::fog::units_below_top;
+use crate::game::solver::Solver;
+use crate::game::stack::constraint::Constraint;
+use crate::game::stack::kind::{Kind, KindId};
+use crate::game::stack::Stack;
+use crate::game::Game;
+use crate::line_reader::LineReader;
+use crate::renderer::Renderer;
+
+// Arrangements tried before giving up on finding a solvable one.
+const SHUFFLE_ATTEMPTS: usize = 5;
+// Shuffles run while the player waits, so each arrangement gets a cheaper search than a hint.
+const SHUFFLE_NODE_LIMIT: usize = 2_000;
+
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
+    pub fn shuffles_left(&self) -> usize {
+        let used: usize = self
+            .ledger
+            .iter()
+            .filter(|entry| matches!(entry, Entry::Shuffle { .. }))
+            .count();
+        self.settings.shuffles.saturating_sub(used)
+    }
+
+    // Stacks that already hold a whole kind are left alone, and so are capped stacks, which
+    // couldn't take the other kinds dealt into them.
+    fn stack_stays_put(&self, stack: &Stack) -> bool {
+        let sorted: bool = stack.get_sole_kind_id().is_some_and(|kind_id| {
+            stack.holds_whole_kind(kind_id, self.get_total_quantity(kind_id))
+        });
+        sorted || matches!(stack.get_constraint(), Some(Constraint::Capped(_)))
+    }
+
+    /// Redistributes the units of unsorted stacks at random, keeping the capacity and occupancy
+    /// of every stack, without taking a turn. Returns false when no solvable arrangement was found.
+    pub fn shuffle_stacks(&mut self) -> bool {
+        let unsorted: Vec<usize> = (0..self.stacks.len())
+            .filter(|ind| !self.stack_stays_put(&self.stacks[*ind]))
+            .collect();
+        let mut unit_ids: Vec<KindId> = unsorted
+            .iter()
+            .flat_map(|ind| self.stacks[*ind].iter_unit_ids())
+            .collect();
+        let solver: Solver = Solver::new(self.units_per_kind.clone(), self.settings.rule_set)
+            .with_node_limit(SHUFFLE_NODE_LIMIT);
+
+        for _ in 0..SHUFFLE_ATTEMPTS {
+            self.random.shuffle(&mut unit_ids);
+            let mut shuffled: Vec<Stack> = self.stacks.clone();
+            let mut hidden: Vec<usize> = self.hidden.clone();
+            let mut remaining = unit_ids.iter();
+            for ind in &unsorted {
+                let mut stack: Stack = self.stacks[*ind].emptied();
+                for unit_id in remaining.by_ref().take(self.stacks[*ind].get_occupancy()) {
+                    stack.push_immigrants(Kind::new(*unit_id, 1));
+                }
+                if self.settings.fog {
+                    hidden[*ind] = units_below_top(&stack);
+                }
+                shuffled[*ind] = stack;
+            }
+            if solver.search(&shuffled).0.is_none() {
+                continue;
+            }
+
+            let previous: Vec<Stack> = std::mem::replace(&mut self.stacks, shuffled);
+            let hidden: Vec<usize> = std::mem::replace(&mut self.hidden, hidden);
+            self.ledger.push(Entry::Shuffle { previous, hidden });
+            self.refresh_kind_status();
+            self.assisted = true;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::Random;
+    use crate::line_reader::MockLineReader;
+
+    #[test]
+    fn test_shuffle_keeps_sorted_stacks_and_undoes() {
+        let mut stage: Game<MockLineReader> = Game::new_from_vecs(
+            vec![
+                vec![3, 3, 0],
+                vec![1, 2, 1, 0],
+                vec![2, 1, 2, 0],
+                vec![0, 0, 0],
+            ],
+            None,
+            MockLineReader::default(),
+        );
+        stage.settings.shuffles = 1;
+        stage.random = Random::new(7);
+        let before: Vec<Stack> = stage.stacks.clone();
+
+        assert!(stage.shuffle_stacks());
+        assert_eq!(stage.shuffles_left(), 0);
+        assert!(stage.stacks[0] == before[0], "Sorted stacks stay put");
+        for (stack, original) in stage.stacks.iter().zip(&before) {
+            assert_eq!(stack.get_capacity(), original.get_capacity());
+            assert_eq!(stack.get_occupancy(), original.get_occupancy());
+        }
+        let solver: Solver = Solver::new(stage.units_per_kind.clone(), stage.settings.rule_set);
+        assert!(solver.search(&stage.stacks).0.is_some());
+
+        stage.undo_move();
+        assert!(stage.stacks == before);
+        assert_eq!(stage.shuffles_left(), 1);
+    }
+
+    #[test]
+    fn test_shuffle_keeps_capped_stacks() {
+        let mut shuffled: usize = 0;
+        for seed in 1..=10 {
+            let mut stage: Game<MockLineReader> = Game::new_from_vecs(
+                vec![vec![1, 0, 0], vec![2, 1, 2], vec![1, 2, 0], vec![0, 0, 0]],
+                None,
+                MockLineReader::default(),
+            );
+            stage.stacks[0].set_constraint(Some(Constraint::Capped(1)));
+            stage.settings.shuffles = 1;
+            stage.random = Random::new(seed);
+            let capped: Stack = stage.stacks[0].clone();
+
+            if stage.shuffle_stacks() {
+                assert!(stage.stacks[0] == capped, "seed {}", seed);
+                shuffled += 1;
+            }
+        }
+        assert!(shuffled > 0);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::game::canonical::canonical_hash;
+use crate::game::rules::RuleSet;
+use crate::game::stack::kind::{IsJoker, Kind, KindId};
+use crate::game::stack::Stack;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+pub type StackMove = (usize, usize);
+
+// Number of positions a single search may expand before giving up.
+const DEFAULT_NODE_LIMIT: usize = 20_000;
+
+// Heuristic weight used once an exact search has run out of nodes, trading optimality for speed.
+const GREEDY_WEIGHT: usize = 4;
+
+pub struct Solution {
+    pub moves: Vec<StackMove>,
+    pub optimal: bool,
+}
+
+/// Statistics gathered while searching for the optimal solution.
+#[derive(Default)]
+pub struct SearchStats {
+    pub explored: usize,
+    pub legal_moves: usize,
+    pub dead_ends: usize,
+}
+
+// A generated position, stored as the move leading to it so that the frontier stays small.
+struct Candidate {
+    parent: Option<usize>,
+    stack_move: Option<StackMove>,
+    depth: usize,
+}
+
+pub struct Solver {
+    units_per_kind: HashMap<KindId, usize>,
+    rule_set: RuleSet,
+    node_limit: usize,
+}
+
+impl Solver {
+    pub fn new(units_per_kind: HashMap<KindId, usize>, rule_set: RuleSet) -> Solver {
+        Solver {
+            units_per_kind,
+            rule_set,
+            node_limit: DEFAULT_NODE_LIMIT,
+        }
+    }
+
+    /// Gives up after exploring `node_limit` boards instead of the default.
+    pub fn with_node_limit(mut self, node_limit: usize) -> Solver {
+        self.node_limit = node_limit;
+        self
+    }
+
+    pub fn apply_move(&self, stacks: &[Stack], stack_move: StackMove) -> Vec<Stack> {
+        let (from, to) = stack_move;
+        let quantity: usize = self.rule_set.movable_quantity(stacks, from, to);
+        let mut next: Vec<Stack> = stacks.to_vec();
+        let kind: Kind = next[from].pop_residents_with_limit(Some(quantity));
+        next[to].push_immigrants(kind);
+        next
+    }
+
+    pub fn is_solved(&self, stacks: &[Stack]) -> bool {
+        stacks.iter().all(|stack| {
+            stack.is_vacant()
+                || stack.get_sole_kind_id().is_some_and(|kind_id| {
+                    stack.holds_whole_kind(kind_id, self.units_per_kind[&kind_id])
+                })
+        })
+    }
+
+    // Every move removes at most one run from the board, and a sorted board has one run per kind.
+    // Jokers may end up anywhere, so their runs aren't counted.
+    fn heuristic(&self, stacks: &[Stack]) -> usize {
+        let runs: usize = stacks.iter().map(Stack::count_kind_runs).sum();
+        let kinds: usize = self
+            .units_per_kind
+            .keys()
+            .filter(|kind_id| !kind_id.is_joker())
+            .count();
+        runs.saturating_sub(kinds)
+    }
+
+    /// Looks for an optimal solution, falling back to a greedy one when the node limit is hit.
+    /// The statistics always describe the exact search.
+    pub fn search(&self, stacks: &[Stack]) -> (Option<Solution>, SearchStats) {
+        let (moves, stats, exhausted) = self.best_first(stacks, 1);
+        let solution: Option<Solution> = match moves {
+            Some(moves) => Some(Solution {
+                moves,
+                optimal: true,
+            }),
+            None if exhausted => None, // Every reachable position was visited.
+            None => self
+                .best_first(stacks, GREEDY_WEIGHT)
+                .0
+                .map(|moves| Solution {
+                    moves,
+                    optimal: false,
+                }),
+        };
+        (solution, stats)
+    }
+
+    // Weighted A*, returns the moves found, the statistics, and whether the search space was exhausted.
+    fn best_first(
+        &self,
+        stacks: &[Stack],
+        weight: usize,
+    ) -> (Option<Vec<StackMove>>, SearchStats, bool) {
+        let mut stats: SearchStats = SearchStats::default();
+        let mut candidates: Vec<Candidate> = vec![Candidate {
+            parent: None,
+            stack_move: None,
+            depth: 0,
+        }];
+        let mut positions: HashMap<usize, Vec<Stack>> = HashMap::new();
+        let mut closed: HashSet<u64> = HashSet::new();
+        let mut frontier: BinaryHeap<Reverse<(usize, Reverse<usize>, usize)>> = BinaryHeap::new();
+        frontier.push(Reverse((self.heuristic(stacks), Reverse(0), 0)));
+
+        while let Some(Reverse((_, _, index))) = frontier.pop() {
+            let position: Vec<Stack> =
+                match (candidates[index].parent, candidates[index].stack_move) {
+                    (Some(parent), Some(stack_move)) => {
+                        self.apply_move(&positions[&parent], stack_move)
+                    }
+                    _ => stacks.to_vec(),
+                };
+            if !closed.insert(canonical_hash(&position)) {
+                continue;
+            }
+            if self.is_solved(&position) {
+                return (Some(Solver::trace(&candidates, index)), stats, false);
+            }
+            if stats.explored == self.node_limit {
+                return (None, stats, false);
+            }
+            stats.explored += 1;
+
+            let moves: Vec<StackMove> = self.rule_set.legal_moves(&position);
+            stats.legal_moves += moves.len();
+            if moves.is_empty() {
+                stats.dead_ends += 1;
+            }
+            let depth: usize = candidates[index].depth + 1;
+            for stack_move in moves {
+                let next: Vec<Stack> = self.apply_move(&position, stack_move);
+                if closed.contains(&canonical_hash(&next)) {
+                    continue;
+                }
+                let priority: usize = depth + weight * self.heuristic(&next);
+                candidates.push(Candidate {
+                    parent: Some(index),
+                    stack_move: Some(stack_move),
+                    depth,
+                });
+                frontier.push(Reverse((priority, Reverse(depth), candidates.len() - 1)));
+            }
+            positions.insert(index, position);
+        }
+        (None, stats, true)
+    }
+
+    fn trace(candidates: &[Candidate], index: usize) -> Vec<StackMove> {
+        let mut moves: Vec<StackMove> = Vec::new();
+        let mut current: &Candidate = &candidates[index];
+        while let (Some(parent), Some(stack_move)) = (current.parent, current.stack_move) {
+            moves.push(stack_move);
+            current = &candidates[parent];
+        }
+        moves.reverse();
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solver_for(stacks: &[Stack]) -> Solver {
+        let mut units_per_kind: HashMap<KindId, usize> = HashMap::new();
+        for unit_id in stacks.iter().flat_map(Stack::iter_unit_ids) {
+            *units_per_kind.entry(unit_id).or_insert(0) += 1;
+        }
+        Solver::new(units_per_kind, RuleSet::Classic)
+    }
+
+    #[test]
+    fn test_solves_first_stage_optimally() {
+        let stacks: Vec<Stack> =
+            Stack::board_from_vecs(vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]]);
+        let solver: Solver = solver_for(&stacks);
+        let solution: Solution = solver.search(&stacks).0.unwrap();
+
+        assert!(solution.optimal);
+        assert_eq!(solution.moves.len(), 3);
+        let solved: Vec<Stack> = solution.moves.iter().fold(stacks, |board, stack_move| {
+            solver.apply_move(&board, *stack_move)
+        });
+        assert!(solver.is_solved(&solved));
+    }
+
+    #[test]
+    fn test_unsolvable_board() {
+        let stacks: Vec<Stack> = Stack::board_from_vecs(vec![vec![1, 2], vec![2, 1]]);
+        let (solution, stats) = solver_for(&stacks).search(&stacks);
+
+        assert!(solution.is_none());
+        assert_eq!(stats.dead_ends, 1);
+    }
+}
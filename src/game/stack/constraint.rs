@@ -0,0 +1,10 @@
+use crate::game::stack::kind::KindId;
+
+/// Level mechanics restricting how units move in and out of a stack.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum Constraint {
+    /// Units can't leave the stack until every unit of the kind is sorted into a single stack.
+    Locked(KindId),
+    /// The stack only accepts units of the kind, and jokers.
+    Capped(KindId),
+}
@@ -1,4 +1,6 @@
 const EMPTY_SLOT_VALUE: KindId = 0;
+const UNKNOWN_SLOT_VALUE: KindId = usize::MAX;
+const JOKER_VALUE: KindId = usize::MAX - 1;
 
 pub type KindId = usize;
 
@@ -10,6 +12,10 @@ pub trait IsEmpty {
     fn is_empty(&self) -> bool;
 }
 
+pub trait IsJoker {
+    fn is_joker(&self) -> bool;
+}
+
 impl HasId for KindId {
     fn get_id(&self) -> KindId {
         *self
@@ -22,7 +28,13 @@ impl IsEmpty for KindId {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Copy, Hash, Ord, PartialOrd)]
+impl IsJoker for KindId {
+    fn is_joker(&self) -> bool {
+        *self == Kind::get_joker_id()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Copy, Hash, Ord, PartialOrd, Debug)]
 pub struct Kind {
     id: KindId,
     quantity: usize,
@@ -40,10 +52,6 @@ impl Kind {
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.id == Kind::get_empty_id()
-    }
-
     pub fn get_id(&self) -> usize {
         self.id
     }
@@ -59,6 +67,16 @@ impl Kind {
     pub fn get_empty_id() -> KindId {
         EMPTY_SLOT_VALUE
     }
+
+    /// Id standing in for units hidden by fog.
+    pub fn get_unknown_id() -> KindId {
+        UNKNOWN_SLOT_VALUE
+    }
+
+    /// Id of jokers, which match every kind and complete alongside any finished stack.
+    pub fn get_joker_id() -> KindId {
+        JOKER_VALUE
+    }
 }
 
 impl HasId for Kind {
@@ -1,15 +1,41 @@
+pub mod constraint;
 pub mod kind;
 
+use std::fmt;
 use std::iter::FlatMap;
 use std::slice::Iter;
 use std::vec::IntoIter;
 
-use kind::{Kind, KindId};
+use constraint::Constraint;
+use kind::{IsEmpty, IsJoker, Kind, KindId};
 
+pub type UnitIds<'a> = FlatMap<Iter<'a, Kind>, IntoIter<KindId>, fn(&Kind) -> IntoIter<KindId>>;
+
+/// Why a list of slots doesn't describe a stack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StackError {
+    /// The slot at this index, counted from the bottom, is empty but has units above it.
+    InteriorEmptySlot(usize),
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackError::InteriorEmptySlot(slot) => write!(
+                f,
+                "Slot {} is empty but has units above it, empty slots must come last",
+                slot + 1
+            ),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub struct Stack {
     capacity: usize,
     occupancy: usize,
     units: Vec<Kind>,
+    constraint: Option<Constraint>,
 }
 
 impl Stack {
@@ -18,44 +44,31 @@ impl Stack {
             capacity,
             occupancy,
             units,
+            constraint: None,
         }
     }
 
-    pub fn clone(&self) -> Stack {
-        Stack::new(
-            self.get_capacity(),
-            self.get_occupancy(),
-            self.units.iter().map(|unit| unit.clone()).collect(),
-        )
-    }
-
-    pub fn new_from_vec(vec: Vec<usize>) -> Stack {
-        let mut units: Vec<Kind> = Vec::new();
-        let mut kind: Kind;
-        let capacity: usize = vec.len();
-        let mut last_unit_id: KindId = Kind::get_empty_id();
-        let mut quantity: usize = 0;
-        let mut occupancy: usize = 0;
-        for (ind, unit_id) in vec.iter().enumerate() {
-            if (*unit_id != last_unit_id) || (ind == capacity - 1) {
-                if last_unit_id != Kind::get_empty_id() {
-                    kind = Kind::new(last_unit_id, quantity);
-                    units.push(kind);
-                    occupancy += quantity;
-                }
-                last_unit_id = *unit_id;
-                quantity = 0;
+    /// Builds a stack from its slots listed from the bottom up, empty slots may only come last.
+    pub fn new_from_vec(vec: Vec<usize>) -> Result<Stack, StackError> {
+        let mut stack: Stack = Stack::new(vec.len(), 0, Vec::new());
+        for (slot, unit_id) in vec.into_iter().enumerate() {
+            if unit_id.is_empty() {
+                continue;
             }
-            quantity += 1;
-        }
-        // Push the last kind if it hasn't been pushed yet
-        if last_unit_id != Kind::get_empty_id() {
-            kind = Kind::new(last_unit_id, quantity);
-            units.push(kind);
-            occupancy += quantity;
+            if stack.occupancy < slot {
+                return Err(StackError::InteriorEmptySlot(stack.occupancy));
+            }
+            stack.push_immigrants(Kind::new(unit_id, 1)); // Joins the run below if it matches.
         }
+        Ok(stack)
+    }
 
-        Stack::new(capacity, occupancy, units)
+    /// Builds a board from the slots of each stack, for tests that spell boards out.
+    #[cfg(test)]
+    pub fn board_from_vecs(vecs: Vec<Vec<usize>>) -> Vec<Stack> {
+        vecs.into_iter()
+            .map(|vec| Stack::new_from_vec(vec).unwrap())
+            .collect()
     }
 
     pub fn is_vacant(&self) -> bool {
@@ -66,13 +79,6 @@ impl Stack {
         self.get_capacity() - self.get_occupancy()
     }
 
-    pub fn clone_top_unit(&self) -> Kind {
-        match self.units.last() {
-            Some(top_resident) => top_resident.clone(),
-            None => Kind::new_empty(),
-        }
-    }
-
     pub fn get_top_unit_id(&self) -> KindId {
         match self.units.last() {
             Some(top_resident) => top_resident.get_id(),
@@ -105,9 +111,9 @@ impl Stack {
 
                 self.occupancy = self.occupancy.saturating_sub(immigrants.get_quantity());
 
-                return immigrants;
+                immigrants
             }
-            None => return Kind::new_empty(),
+            None => Kind::new_empty(),
         }
     }
 
@@ -116,6 +122,10 @@ impl Stack {
     }
 
     pub fn push_immigrants(&mut self, immigrants: Kind) {
+        // Nothing arrives from an empty stack.
+        if immigrants.get_quantity() == 0 {
+            return;
+        }
         let last_resident = self.units.last_mut();
 
         match last_resident {
@@ -130,9 +140,7 @@ impl Stack {
         self.occupancy += immigrants.get_quantity();
     }
 
-    pub fn iter_unit_ids(
-        &self,
-    ) -> FlatMap<Iter<'_, Kind>, IntoIter<KindId>, fn(&Kind) -> IntoIter<KindId>> {
+    pub fn iter_unit_ids(&self) -> UnitIds<'_> {
         fn unit_to_ids(unit: &Kind) -> IntoIter<KindId> {
             vec![unit.get_id(); unit.get_quantity()].into_iter()
         }
@@ -140,6 +148,65 @@ impl Stack {
         self.units.iter().flat_map(unit_to_ids)
     }
 
+    pub fn get_constraint(&self) -> Option<Constraint> {
+        self.constraint
+    }
+
+    pub fn set_constraint(&mut self, constraint: Option<Constraint>) {
+        self.constraint = constraint;
+    }
+
+    /// A copy of the stack without its units.
+    pub fn emptied(&self) -> Stack {
+        let mut emptied: Stack = Stack::new(self.capacity, 0, Vec::new());
+        emptied.constraint = self.constraint;
+        emptied
+    }
+
+    /// A copy of the stack where the `hidden` bottom units are replaced by units of `unknown_id`.
+    pub fn masked(&self, hidden: usize, unknown_id: KindId) -> Stack {
+        let mut masked: Stack = self.emptied();
+        masked.push_immigrants(Kind::new(unknown_id, hidden));
+        for unit_id in self.iter_unit_ids().skip(hidden) {
+            masked.push_immigrants(Kind::new(unit_id, 1));
+        }
+        masked
+    }
+
+    pub fn count_runs(&self) -> usize {
+        self.units.len()
+    }
+
+    /// Runs of units other than jokers.
+    pub fn count_kind_runs(&self) -> usize {
+        self.units
+            .iter()
+            .filter(|unit| !unit.get_id().is_joker())
+            .count()
+    }
+
+    pub fn count_units(&self, kind_id: KindId) -> usize {
+        self.iter_unit_ids()
+            .filter(|unit_id| *unit_id == kind_id)
+            .count()
+    }
+
+    /// The kind shared by every unit but jokers, if there is exactly one.
+    pub fn get_sole_kind_id(&self) -> Option<KindId> {
+        let mut kind_ids = self
+            .units
+            .iter()
+            .map(Kind::get_id)
+            .filter(|unit_id| !unit_id.is_joker());
+        let first: KindId = kind_ids.next()?;
+        kind_ids.all(|unit_id| unit_id == first).then_some(first)
+    }
+
+    /// Whether the stack holds all `total` units of its kind, alongside jokers only.
+    pub fn holds_whole_kind(&self, kind_id: KindId, total: usize) -> bool {
+        self.get_sole_kind_id() == Some(kind_id) && self.count_units(kind_id) == total
+    }
+
     pub fn get_occupancy(&self) -> usize {
         self.occupancy
     }
@@ -148,3 +215,148 @@ impl Stack {
         self.capacity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::random::Random;
+    use crate::game::rules::RuleSet;
+    use crate::game::Game;
+    use crate::line_reader::MockLineReader;
+
+    // Random cases per property, each from its own seed so that failures can be replayed.
+    const CASES: u64 = 500;
+    const MOVES_PER_CASE: usize = 50;
+
+    // Stack contents filled from the bottom up, with few kinds so that runs form often.
+    fn random_vec(random: &mut Random) -> Vec<usize> {
+        let capacity: usize = 1 + random.below(6);
+        let occupancy: usize = random.below(capacity + 1);
+        (0..capacity)
+            .map(|slot| match slot < occupancy {
+                true => 1 + random.below(3),
+                false => Kind::get_empty_id(),
+            })
+            .collect()
+    }
+
+    fn assert_invariants(stack: &Stack) {
+        let total: usize = stack.units.iter().map(Kind::get_quantity).sum();
+        assert_eq!(stack.occupancy, total, "Occupancy of {:?}", stack.units);
+        assert!(stack.occupancy <= stack.capacity);
+        assert!(stack.units.iter().all(|unit| unit.get_quantity() > 0));
+        assert!(
+            stack
+                .units
+                .windows(2)
+                .all(|pair| pair[0].get_id() != pair[1].get_id()),
+            "Adjacent runs of the same kind in {:?}",
+            stack.units
+        );
+    }
+
+    fn round_trip(stack: &Stack) -> Stack {
+        let mut vec: Vec<usize> = stack.iter_unit_ids().collect();
+        vec.resize(stack.get_capacity(), Kind::get_empty_id());
+        Stack::new_from_vec(vec).unwrap()
+    }
+
+    // Slots from the bottom up, and the runs of (kind, quantity) they make or the error they raise.
+    type Case = (Vec<usize>, Result<Vec<(KindId, usize)>, StackError>);
+
+    #[test]
+    fn test_new_from_vec() {
+        let joker: KindId = Kind::get_joker_id();
+        let table: Vec<Case> = vec![
+            (vec![], Ok(vec![])),
+            (vec![0], Ok(vec![])),
+            (vec![0, 0, 0], Ok(vec![])),
+            (vec![1], Ok(vec![(1, 1)])),
+            (vec![1, 0], Ok(vec![(1, 1)])),
+            (vec![1, 1], Ok(vec![(1, 2)])),
+            // Full stacks once had their top unit split off the run below it.
+            (vec![1, 1, 1], Ok(vec![(1, 3)])),
+            (vec![1, 1, 0], Ok(vec![(1, 2)])),
+            (vec![1, 2], Ok(vec![(1, 1), (2, 1)])),
+            (vec![2, 1, 1], Ok(vec![(2, 1), (1, 2)])),
+            (vec![1, 2, 1], Ok(vec![(1, 1), (2, 1), (1, 1)])),
+            (vec![1, 1, 2, 2, 0], Ok(vec![(1, 2), (2, 2)])),
+            (vec![1, 2, 2, 2], Ok(vec![(1, 1), (2, 3)])),
+            (
+                vec![joker, 1, joker, 0],
+                Ok(vec![(joker, 1), (1, 1), (joker, 1)]),
+            ),
+            (vec![0, 1], Err(StackError::InteriorEmptySlot(0))),
+            (vec![0, 0, 1], Err(StackError::InteriorEmptySlot(0))),
+            (vec![1, 0, 1], Err(StackError::InteriorEmptySlot(1))),
+            (vec![1, 0, 2], Err(StackError::InteriorEmptySlot(1))),
+            (vec![1, 1, 0, 2, 0], Err(StackError::InteriorEmptySlot(2))),
+        ];
+
+        for (vec, expected) in table {
+            let capacity: usize = vec.len();
+            let expected: Result<Stack, StackError> = expected.map(|runs| {
+                let units: Vec<Kind> = runs.iter().map(|&(id, qty)| Kind::new(id, qty)).collect();
+                Stack::new(capacity, runs.iter().map(|run| run.1).sum(), units)
+            });
+            assert_eq!(Stack::new_from_vec(vec.clone()), expected, "{:?}", vec);
+        }
+    }
+
+    #[test]
+    fn test_stacks_from_vecs_keep_invariants() {
+        for seed in 1..=CASES {
+            let mut random: Random = Random::new(seed);
+            let vec: Vec<usize> = random_vec(&mut random);
+            let stack: Stack = Stack::new_from_vec(vec.clone()).unwrap();
+
+            assert_invariants(&stack);
+            assert_eq!(stack.get_capacity(), vec.len());
+            let unit_ids: Vec<usize> = stack.iter_unit_ids().collect();
+            let expected: Vec<usize> = vec.into_iter().filter(|id| !id.is_empty()).collect();
+            assert_eq!(unit_ids, expected, "seed {}", seed);
+            assert_eq!(round_trip(&stack), stack, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_moves_keep_invariants_and_undo() {
+        let mut moves_made: usize = 0;
+        for seed in 1..=CASES {
+            let mut random: Random = Random::new(seed);
+            let vecs: Vec<Vec<usize>> = (0..4).map(|_| random_vec(&mut random)).collect();
+            let mut stage: Game<MockLineReader> =
+                Game::new_from_vecs(vecs, None, MockLineReader::default());
+            let rule_set: RuleSet = RuleSet::ALL[seed as usize % RuleSet::ALL.len()];
+            stage.settings.rule_set = rule_set;
+            let mut boards: Vec<Vec<Stack>> = Vec::new();
+
+            for _ in 0..MOVES_PER_CASE {
+                let moves: Vec<(usize, usize)> = rule_set.legal_moves(&stage.stacks);
+                if moves.is_empty() {
+                    break;
+                }
+                let (from, to) = moves[random.below(moves.len())];
+                boards.push(stage.stacks.clone());
+                moves_made += 1;
+                if rule_set.allows_counts() {
+                    let movable: usize = rule_set.movable_quantity(&stage.stacks, from, to);
+                    stage.move_quantity_legally(from, to, 1 + random.below(movable));
+                } else {
+                    stage.move_legally(from, to);
+                }
+                for stack in &stage.stacks {
+                    assert_invariants(stack);
+                    assert_eq!(round_trip(stack), *stack, "seed {}", seed);
+                }
+            }
+
+            // Undoing through the ledger walks back through every board, down to the first one.
+            while let Some(board) = boards.pop() {
+                stage.undo_move();
+                assert_eq!(stage.stacks, board, "seed {}", seed);
+            }
+        }
+        assert!(moves_made > CASES as usize, "Most cases make several moves");
+    }
+}
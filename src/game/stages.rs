@@ -1,27 +1,33 @@
 use crate::game::stack::Stack;
 use crate::game::Game;
 use crate::line_reader::LineReader;
+use crate::renderer::Renderer;
+use std::collections::HashSet;
 
-impl<TLR: LineReader + Default> Game<TLR> {
+impl<TLR: LineReader + Default, TRD: Renderer> Game<TLR, TRD> {
     fn vecs_to_stacks(vecs: Vec<Vec<usize>>) -> Vec<Stack> {
         let mut stacks: Vec<Stack> = Vec::new();
         let mut stack: Stack;
         for vec in vecs {
-            stack = Stack::new_from_vec(vec);
+            stack = Stack::new_from_vec(vec).expect("Stages list units before empty slots");
             stacks.push(stack);
         }
         stacks
     }
 
-    fn new_from_vecs(
+    pub fn new_from_vecs(
         vecs: Vec<Vec<usize>>,
         stage_name: Option<String>,
         line_reader: TLR,
-    ) -> Game<TLR> {
-        Game::new(Game::<TLR>::vecs_to_stacks(vecs), stage_name, line_reader)
+    ) -> Game<TLR, TRD> {
+        Game::new(
+            Game::<TLR, TRD>::vecs_to_stacks(vecs),
+            stage_name,
+            line_reader,
+        )
     }
 
-    pub fn get_stages(line_reader: TLR) -> Vec<Game<TLR>> {
+    pub fn get_stages(line_reader: TLR) -> Vec<Game<TLR, TRD>> {
         let stage_vec: Vec<Vec<Vec<usize>>> = vec![
             vec![vec![2, 1, 0], vec![1, 2], vec![2, 0]],
             vec![
@@ -56,7 +62,7 @@ impl<TLR: LineReader + Default> Game<TLR> {
                 vec![5, 2, 5],
             ],
         ];
-        let mut stages: Vec<Game<TLR>> = Vec::new();
+        let mut stages: Vec<Game<TLR, TRD>> = Vec::new();
         for (ind, vec_stacks) in stage_vec.iter().enumerate() {
             let name: String = format!("Stage - {}", ind + 1);
             stages.push(Game::new_from_vecs(
@@ -67,4 +73,13 @@ impl<TLR: LineReader + Default> Game<TLR> {
         }
         stages
     }
+
+    /// Drops stages whose layout repeats an earlier one, up to the order of the stacks.
+    pub fn dedup_stages(stages: Vec<Game<TLR, TRD>>) -> Vec<Game<TLR, TRD>> {
+        let mut seen: HashSet<u64> = HashSet::new();
+        stages
+            .into_iter()
+            .filter(|stage| seen.insert(stage.canonical_hash()))
+            .collect()
+    }
 }
@@ -1,2 +1,4 @@
 pub mod game;
 pub mod line_reader;
+pub mod profile;
+pub mod renderer;
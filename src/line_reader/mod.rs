@@ -1,9 +1,61 @@
+mod script;
+
+pub use script::ScriptReader;
+
 use std::cell::Cell;
 use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+// Lines read from stdin by a background thread, so that reads can time out.
+static STDIN_LINES: OnceLock<Mutex<Receiver<io::Result<String>>>> = OnceLock::new();
+
+fn stdin_lines() -> &'static Mutex<Receiver<io::Result<String>>> {
+    STDIN_LINES.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<io::Result<String>>();
+        thread::spawn(move || loop {
+            let mut line: String = String::new();
+            // Dropping the sender tells readers that stdin is closed.
+            let read: io::Result<String> = match io::stdin().read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => Ok(line),
+                Err(error) => Err(error),
+            };
+            let failed: bool = read.is_err();
+            if sender.send(read).is_err() || failed {
+                break;
+            }
+        });
+        Mutex::new(receiver)
+    })
+}
+
+fn push_line(input: &mut String, read: io::Result<String>) -> io::Result<usize> {
+    let line: String = read?;
+    input.push_str(&line);
+    Ok(line.len())
+}
 
 impl LineReader for STDInReader {
-    fn read_line(&self, input: &mut String) {
-        io::stdin().read_line(input).unwrap();
+    fn read_line(&self, input: &mut String) -> io::Result<usize> {
+        match stdin_lines().lock().unwrap().recv() {
+            Ok(read) => push_line(input, read),
+            Err(_) => Ok(0),
+        }
+    }
+
+    fn read_line_timeout(
+        &self,
+        input: &mut String,
+        timeout: Duration,
+    ) -> Option<io::Result<usize>> {
+        match stdin_lines().lock().unwrap().recv_timeout(timeout) {
+            Ok(read) => Some(push_line(input, read)),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Ok(0)),
+        }
     }
 }
 
@@ -23,16 +75,35 @@ impl Default for MockLineReader {
 }
 
 impl LineReader for MockLineReader {
-    fn read_line(&self, input: &mut String) {
+    fn read_line(&self, input: &mut String) -> io::Result<usize> {
         let index = self.index.get();
-        let line = self.lines.get(index);
+        let Some(line) = self.lines.get(index) else {
+            return Ok(0);
+        };
         self.index.set(index + 1);
-        *input = line.unwrap().clone();
+        *input = line.clone();
+        Ok(line.len() + 1) // Count the newline, so that blank lines aren't taken for the end.
     }
 }
 
 pub trait LineReader: Default + Clone {
-    fn read_line(&self, input: &mut String);
+    /// Reads a line into `input`, returning the number of bytes read, 0 once input has ended.
+    fn read_line(&self, input: &mut String) -> io::Result<usize>;
+
+    /// Reads a line unless `timeout` passes first, returning `None` if it did.
+    /// Readers that can't time out block until a line is available.
+    fn read_line_timeout(
+        &self,
+        input: &mut String,
+        _timeout: Duration,
+    ) -> Option<io::Result<usize>> {
+        Some(self.read_line(input))
+    }
+
+    /// Whether lines come from a script rather than being typed, so that the game echoes them.
+    fn is_scripted(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -46,9 +117,10 @@ mod tests {
     fn test_mock_line_reader() {
         let mock = MockLineReader::default();
         let mut input = String::new();
-        mock.read_line(&mut input);
+        mock.read_line(&mut input).unwrap();
         assert!(input.eq("2 3"));
-        mock.read_line(&mut input);
+        mock.read_line(&mut input).unwrap();
         assert!(input.eq("1 2"));
+        assert_eq!(mock.read_line(&mut input).unwrap(), 0);
     }
 }
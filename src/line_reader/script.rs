@@ -0,0 +1,74 @@
+use crate::line_reader::LineReader;
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Reads commands from a script, one per line, so that stages can be played without a terminal.
+/// Lines starting with '#' are skipped, and clones share their position in the script.
+#[derive(Clone, Default)]
+pub struct ScriptReader {
+    lines: Rc<Vec<String>>,
+    index: Rc<Cell<usize>>,
+}
+
+impl ScriptReader {
+    pub fn new(script: &str) -> ScriptReader {
+        let lines: Vec<String> = script
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        ScriptReader {
+            lines: Rc::new(lines),
+            index: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<ScriptReader> {
+        Ok(ScriptReader::new(&fs::read_to_string(path)?))
+    }
+}
+
+impl LineReader for ScriptReader {
+    fn read_line(&self, input: &mut String) -> io::Result<usize> {
+        let index: usize = self.index.get();
+        let Some(line) = self.lines.get(index) else {
+            return Ok(0);
+        };
+        self.index.set(index + 1);
+        input.push_str(line);
+        Ok(line.len() + 1) // Count the newline, so that blank lines aren't taken for the end.
+    }
+
+    fn is_scripted(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_reader() {
+        let script: ScriptReader = ScriptReader::new("# Solves the first stage\n2 3\n\n1 2");
+        let clone: ScriptReader = script.clone();
+        let mut input: String = String::new();
+
+        script.read_line(&mut input).unwrap();
+        assert_eq!(input, "2 3");
+        input.clear();
+        assert_eq!(
+            clone.read_line(&mut input).unwrap(),
+            1,
+            "Blank lines stand for Enter"
+        );
+        script.read_line(&mut input).unwrap();
+        assert_eq!(input, "1 2");
+        input.clear();
+        assert_eq!(clone.read_line(&mut input).unwrap(), 0);
+        assert!(input.is_empty());
+    }
+}
@@ -1,5 +1,101 @@
 use sorting_game::game::Game;
-use sorting_game::line_reader::STDInReader;
+use sorting_game::line_reader::{LineReader, STDInReader, ScriptReader};
+use sorting_game::profile::leaderboard::Leaderboard;
+use sorting_game::profile::Profile;
+use sorting_game::renderer::StdoutRenderer;
+use std::path::PathBuf;
+
+const USAGE: &str =
+    "Usage: sorting-game [--levels <file>] [--script <file>] [--timed] [--no-color] | stats | scores [--reset]";
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn exit_with_error(error: impl std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
+
+fn load_or_exit<T>(loaded: std::io::Result<T>, what: &str) -> T {
+    loaded.unwrap_or_else(|error| exit_with_error(format!("Could not load {}: {}", what, error)))
+}
+
+#[derive(Clone, Copy)]
+struct Options {
+    timed: bool,
+    color: bool,
+}
+
+fn play_stages<TLR: LineReader>(line_reader: TLR, levels: Option<PathBuf>, options: Options) {
+    let mut stages: Vec<Game<TLR>> = match levels {
+        Some(path) => match Game::load_stages(&path, line_reader) {
+            Ok(stages) => Game::sort_by_difficulty(Game::dedup_stages(stages)),
+            Err(error) => exit_with_error(error),
+        },
+        None => Game::get_stages(line_reader),
+    };
+    for stage in stages.iter_mut() {
+        if options.timed {
+            stage.enable_clock();
+        }
+        stage.set_renderer(StdoutRenderer::new(options.color));
+    }
+    Game::play_stages(stages);
+}
+
+fn play(args: &[String]) {
+    let mut levels: Option<PathBuf> = None;
+    let mut script: Option<PathBuf> = None;
+    let mut options: Options = Options {
+        timed: false,
+        color: true,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--levels" => match args.next() {
+                Some(path) => levels = Some(PathBuf::from(path)),
+                None => exit_with_usage(),
+            },
+            "--script" => match args.next() {
+                Some(path) => script = Some(PathBuf::from(path)),
+                None => exit_with_usage(),
+            },
+            "--timed" => options.timed = true,
+            "--no-color" => options.color = false,
+            _ => exit_with_usage(),
+        }
+    }
+
+    match script {
+        Some(path) => match ScriptReader::load(&path) {
+            Ok(script) => play_stages(script, levels, options),
+            Err(error) => exit_with_error(format!("Could not read script: {}", error)),
+        },
+        None => play_stages(STDInReader::default(), levels, options),
+    }
+}
+
 fn main() {
-    Game::<STDInReader>::play(STDInReader::default());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        ["stats"] => print!(
+            "{}",
+            load_or_exit(Profile::load_default(), "profile").summary()
+        ),
+        ["scores"] => print!(
+            "{}",
+            load_or_exit(Leaderboard::load_default(), "scores").summary()
+        ),
+        ["scores", "--reset"] => {
+            let mut leaderboard: Leaderboard = load_or_exit(Leaderboard::load_default(), "scores");
+            leaderboard.reset();
+            if let Err(error) = leaderboard.save() {
+                exit_with_error(format!("Could not save scores: {}", error));
+            }
+        }
+        _ => play(&args),
+    }
 }
@@ -0,0 +1,256 @@
+use crate::profile::{data_dir, format_duration, read_rows, write_rows};
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LEADERBOARD_FILE_NAME: &str = "scores.tsv";
+const LEADERBOARD_HEADER: &str = "# sorting-game scores v1";
+
+// Number of scores kept per stage.
+pub const TABLE_SIZE: usize = 10;
+
+/// Name recorded with new scores, `$SORTING_GAME_PLAYER` or the login name.
+pub fn player_name() -> String {
+    env::var("SORTING_GAME_PLAYER")
+        .or_else(|_| env::var("USER"))
+        .unwrap_or("Player".to_string())
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Formats a Unix timestamp as a `YYYY-MM-DD` UTC date.
+pub fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's days-to-civil conversion.
+    let days: i64 = (timestamp / 86_400) as i64 + 719_468;
+    let era: i64 = days.div_euclid(146_097);
+    let day_of_era: i64 = days.rem_euclid(146_097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: i64 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year: i64 = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Score {
+    pub player: String,
+    pub moves: usize,
+    pub time: Duration,
+    pub date: u64,
+}
+
+impl Score {
+    // Fewer moves first, then faster times, then older scores.
+    fn rank_key(&self) -> (usize, Duration, u64) {
+        (self.moves, self.time, self.date)
+    }
+}
+
+#[derive(Clone, Default)]
+struct Table {
+    stage_name: String,
+    scores: Vec<Score>,
+}
+
+/// Top scores of every stage, keyed by the hash of the stage's initial layout so that
+/// renaming a stage keeps its scores.
+#[derive(Clone, Default)]
+pub struct Leaderboard {
+    path: Option<PathBuf>,
+    tables: BTreeMap<u64, Table>,
+}
+
+impl Leaderboard {
+    pub fn load(path: PathBuf) -> io::Result<Leaderboard> {
+        let mut leaderboard: Leaderboard = Leaderboard {
+            path: Some(path.clone()),
+            ..Leaderboard::default()
+        };
+        for row in read_rows(&path)? {
+            let fields: Vec<&str> = row.iter().map(String::as_str).collect();
+            let ["score", key, moves, millis, date, player, stage_name] = fields[..] else {
+                continue; // Malformed lines.
+            };
+            let (Ok(layout_hash), Ok(moves), Ok(millis), Ok(date)) = (
+                u64::from_str_radix(key, 16),
+                moves.parse::<usize>(),
+                millis.parse::<u64>(),
+                date.parse::<u64>(),
+            ) else {
+                continue;
+            };
+            let table: &mut Table = leaderboard.tables.entry(layout_hash).or_default();
+            table.stage_name = stage_name.to_string();
+            table.scores.push(Score {
+                player: player.to_string(),
+                moves,
+                time: Duration::from_millis(millis),
+                date,
+            });
+        }
+        for table in leaderboard.tables.values_mut() {
+            table.scores.sort_by_key(Score::rank_key);
+            table.scores.truncate(TABLE_SIZE);
+        }
+        Ok(leaderboard)
+    }
+
+    pub fn load_default() -> io::Result<Leaderboard> {
+        match data_dir() {
+            Some(dir) => Leaderboard::load(dir.join(LEADERBOARD_FILE_NAME)),
+            None => Ok(Leaderboard::default()),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for (layout_hash, table) in &self.tables {
+            for score in &table.scores {
+                rows.push(vec![
+                    "score".to_string(),
+                    format!("{:016x}", layout_hash),
+                    score.moves.to_string(),
+                    score.time.as_millis().to_string(),
+                    score.date.to_string(),
+                    score.player.clone(),
+                    table.stage_name.clone(),
+                ]);
+            }
+        }
+        write_rows(path, LEADERBOARD_HEADER, rows)
+    }
+
+    pub fn get_scores(&self, layout_hash: u64) -> &[Score] {
+        self.tables
+            .get(&layout_hash)
+            .map_or(&[], |table| table.scores.as_slice())
+    }
+
+    /// Adds a score to the stage's table, returning its rank if it made the cut.
+    pub fn submit(&mut self, layout_hash: u64, stage_name: &str, score: Score) -> Option<usize> {
+        let table: &mut Table = self.tables.entry(layout_hash).or_default();
+        table.stage_name = stage_name.to_string();
+        let rank: usize = table
+            .scores
+            .partition_point(|other| other.rank_key() <= score.rank_key());
+        if rank >= TABLE_SIZE {
+            return None;
+        }
+        table.scores.insert(rank, score);
+        table.scores.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+
+    pub fn reset(&mut self) {
+        self.tables.clear();
+    }
+
+    /// Renders one stage's table, marking the row at `highlight`.
+    pub fn format_table(&self, layout_hash: u64, highlight: Option<usize>) -> String {
+        let mut text: String = format!(
+            "{:>3} {:<16} {:>5} {:>6} {:>10}\n",
+            "#", "Player", "Moves", "Time", "Date"
+        );
+        for (rank, score) in self.get_scores(layout_hash).iter().enumerate() {
+            text.push_str(&format!(
+                "{:>3} {:<16} {:>5} {:>6} {:>10}{}\n",
+                rank + 1,
+                score.player,
+                score.moves,
+                format_duration(score.time),
+                format_date(score.date),
+                if highlight == Some(rank) { " <-" } else { "" },
+            ));
+        }
+        text
+    }
+
+    pub fn summary(&self) -> String {
+        if self.tables.is_empty() {
+            return "No scores yet\n".to_string();
+        }
+        let mut summary: String = String::new();
+        for (layout_hash, table) in &self.tables {
+            summary.push_str(&format!("{}\n", table.stage_name));
+            summary.push_str(&self.format_table(*layout_hash, None));
+            summary.push('\n');
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn score(player: &str, moves: usize, seconds: u64) -> Score {
+        Score {
+            player: player.to_string(),
+            moves,
+            time: Duration::from_secs(seconds),
+            date: 0,
+        }
+    }
+
+    #[test]
+    fn test_submit_keeps_the_top_scores() {
+        let mut leaderboard: Leaderboard = Leaderboard::default();
+        for moves in 10..10 + TABLE_SIZE {
+            leaderboard.submit(1, "Stage", score("a", moves, 60));
+        }
+
+        assert_eq!(leaderboard.submit(1, "Stage", score("b", 30, 1)), None);
+        assert_eq!(leaderboard.submit(1, "Stage", score("c", 12, 30)), Some(2));
+        assert_eq!(leaderboard.submit(1, "Stage", score("d", 12, 90)), Some(4));
+        assert_eq!(leaderboard.get_scores(1).len(), TABLE_SIZE);
+        assert_eq!(leaderboard.get_scores(1)[2].player, "c");
+        assert!(leaderboard.get_scores(2).is_empty());
+
+        leaderboard.reset();
+        assert!(leaderboard.get_scores(1).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path: PathBuf = env::temp_dir()
+            .join(format!("sorting-game-scores-{}", std::process::id()))
+            .join(LEADERBOARD_FILE_NAME);
+        let mut leaderboard: Leaderboard = Leaderboard::load(path.clone()).unwrap();
+        leaderboard.submit(u64::MAX, "Stage - 1", score("Ada Lovelace", 3, 12));
+        leaderboard.submit(u64::MAX, "Stage - 1", score("Alan", 4, 5));
+        leaderboard.save().unwrap();
+
+        let loaded: Leaderboard = Leaderboard::load(path.clone()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(
+            loaded.get_scores(u64::MAX),
+            leaderboard.get_scores(u64::MAX)
+        );
+        assert_eq!(loaded.summary(), leaderboard.summary());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_792_368_000), "2026-10-19");
+    }
+}
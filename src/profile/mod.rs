@@ -0,0 +1,254 @@
+pub mod leaderboard;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const PROFILE_FILE_NAME: &str = "profile.tsv";
+const PROFILE_HEADER: &str = "# sorting-game profile v1";
+
+/// Directory holding the player's files, `$SORTING_GAME_HOME` or `~/.sorting-game`.
+pub fn data_dir() -> Option<PathBuf> {
+    match env::var_os("SORTING_GAME_HOME") {
+        Some(home) => Some(PathBuf::from(home)),
+        None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".sorting-game")),
+    }
+}
+
+// Rows of a tab separated file split into fields, without comments. A file that doesn't exist
+// yet has no rows.
+fn read_rows(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    let text: String = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    Ok(text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split('\t').map(str::to_string).collect())
+        .collect())
+}
+
+// Writes the header and the rows, creating the directory if needed. Tabs within fields are
+// replaced, so that they can't shift the columns.
+fn write_rows(path: &Path, header: &str, rows: Vec<Vec<String>>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut text: String = format!("{}\n", header);
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|field| field.replace('\t', " ")).collect();
+        text.push_str(&fields.join("\t"));
+        text.push('\n');
+    }
+    fs::write(path, text)
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds: u64 = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct StageRecord {
+    pub name: String,
+    pub attempts: usize,
+    pub resets: usize,
+    pub undos: usize,
+    pub completions: usize,
+    pub best_moves: Option<usize>,
+    pub best_time: Option<Duration>,
+}
+
+/// Per-stage statistics, keyed by the hash of each stage's initial layout.
+#[derive(Clone, Default)]
+pub struct Profile {
+    path: Option<PathBuf>,
+    stages_completed: usize,
+    records: BTreeMap<u64, StageRecord>,
+}
+
+fn parse_optional(field: &str) -> Option<u64> {
+    field.parse::<u64>().ok()
+}
+
+fn format_optional(value: Option<u64>) -> String {
+    value.map_or("-".to_string(), |value| value.to_string())
+}
+
+impl Profile {
+    /// Loads the profile at `path`, starting a fresh one if the file doesn't exist yet.
+    /// Lines that can't be parsed are skipped.
+    pub fn load(path: PathBuf) -> io::Result<Profile> {
+        let mut profile: Profile = Profile {
+            path: Some(path.clone()),
+            ..Profile::default()
+        };
+        for row in read_rows(&path)? {
+            let fields: Vec<&str> = row.iter().map(String::as_str).collect();
+            match fields[..] {
+                ["stages_completed", count] => {
+                    profile.stages_completed = count.parse().unwrap_or(0);
+                }
+                ["stage", key, attempts, resets, undos, completions, moves, millis, name] => {
+                    let Ok(layout_hash) = u64::from_str_radix(key, 16) else {
+                        continue;
+                    };
+                    profile.records.insert(
+                        layout_hash,
+                        StageRecord {
+                            name: name.to_string(),
+                            attempts: attempts.parse().unwrap_or(0),
+                            resets: resets.parse().unwrap_or(0),
+                            undos: undos.parse().unwrap_or(0),
+                            completions: completions.parse().unwrap_or(0),
+                            best_moves: parse_optional(moves).map(|moves| moves as usize),
+                            best_time: parse_optional(millis).map(Duration::from_millis),
+                        },
+                    );
+                }
+                _ => {} // Malformed lines.
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Loads the profile from the data directory, or keeps it in memory if there's none.
+    pub fn load_default() -> io::Result<Profile> {
+        match data_dir() {
+            Some(dir) => Profile::load(dir.join(PROFILE_FILE_NAME)),
+            None => Ok(Profile::default()),
+        }
+    }
+
+    /// Writes the profile back to where it was loaded from, in-memory profiles aren't saved.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut rows: Vec<Vec<String>> = vec![vec![
+            "stages_completed".to_string(),
+            self.stages_completed.to_string(),
+        ]];
+        for (layout_hash, record) in &self.records {
+            rows.push(vec![
+                "stage".to_string(),
+                format!("{:016x}", layout_hash),
+                record.attempts.to_string(),
+                record.resets.to_string(),
+                record.undos.to_string(),
+                record.completions.to_string(),
+                format_optional(record.best_moves.map(|moves| moves as u64)),
+                format_optional(record.best_time.map(|time| time.as_millis() as u64)),
+                record.name.clone(),
+            ]);
+        }
+        write_rows(path, PROFILE_HEADER, rows)
+    }
+
+    pub fn get_stages_completed(&self) -> usize {
+        self.stages_completed
+    }
+
+    pub fn get_record(&self, layout_hash: u64) -> Option<&StageRecord> {
+        self.records.get(&layout_hash)
+    }
+
+    pub fn record_attempt(&mut self, layout_hash: u64, name: &str) {
+        let record: &mut StageRecord = self.records.entry(layout_hash).or_default();
+        record.name = name.to_string();
+        record.attempts += 1;
+    }
+
+    pub fn record_reset(&mut self, layout_hash: u64) {
+        self.records.entry(layout_hash).or_default().resets += 1;
+    }
+
+    pub fn record_undo(&mut self, layout_hash: u64) {
+        self.records.entry(layout_hash).or_default().undos += 1;
+    }
+
+    pub fn record_completion(&mut self, layout_hash: u64, moves: usize, time: Duration) {
+        let record: &mut StageRecord = self.records.entry(layout_hash).or_default();
+        record.completions += 1;
+        record.best_moves = Some(record.best_moves.map_or(moves, |best| best.min(moves)));
+        record.best_time = Some(record.best_time.map_or(time, |best| best.min(time)));
+        self.stages_completed += 1;
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary: String = format!("Stages completed - {}\n\n", self.stages_completed);
+        summary.push_str(&format!(
+            "{:<20} {:>8} {:>6} {:>6} {:>10} {:>9}\n",
+            "Stage", "Attempts", "Resets", "Undos", "Best moves", "Best time"
+        ));
+        let mut records: Vec<&StageRecord> = self.records.values().collect();
+        records.sort_by(|a, b| a.name.cmp(&b.name));
+        for record in records {
+            summary.push_str(&format!(
+                "{:<20} {:>8} {:>6} {:>6} {:>10} {:>9}\n",
+                record.name,
+                record.attempts,
+                record.resets,
+                record.undos,
+                record
+                    .best_moves
+                    .map_or("-".to_string(), |moves| moves.to_string()),
+                record.best_time.map_or("-".to_string(), format_duration),
+            ));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_keep_the_best_results() {
+        let mut profile: Profile = Profile::default();
+        profile.record_attempt(7, "Stage - 1");
+        profile.record_undo(7);
+        profile.record_completion(7, 5, Duration::from_secs(30));
+        profile.record_attempt(7, "Stage - 1");
+        profile.record_reset(7);
+        profile.record_completion(7, 3, Duration::from_secs(45));
+
+        let record: &StageRecord = profile.get_record(7).unwrap();
+        assert_eq!(record.attempts, 2);
+        assert_eq!(record.resets, 1);
+        assert_eq!(record.undos, 1);
+        assert_eq!(record.best_moves, Some(3));
+        assert_eq!(record.best_time, Some(Duration::from_secs(30)));
+        assert_eq!(profile.get_stages_completed(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path: PathBuf = env::temp_dir()
+            .join(format!("sorting-game-profile-{}", std::process::id()))
+            .join(PROFILE_FILE_NAME);
+        let mut profile: Profile = Profile::load(path.clone()).unwrap();
+        profile.record_attempt(u64::MAX, "Stage - 2");
+        profile.record_attempt(1, "Unfinished");
+        profile.record_completion(u64::MAX, 40, Duration::from_millis(61_500));
+        profile.save().unwrap();
+
+        let loaded: Profile = Profile::load(path.clone()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(loaded.get_stages_completed(), 1);
+        assert_eq!(loaded.get_record(u64::MAX), profile.get_record(u64::MAX));
+        assert_eq!(loaded.get_record(1), profile.get_record(1));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(61_500)), "1:01");
+        assert_eq!(format_duration(Duration::from_secs(600)), "10:00");
+    }
+}
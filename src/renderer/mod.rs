@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+pub trait Renderer: Default + Clone {
+    /// Writes `text` as is, without adding a newline.
+    fn print(&self, text: &str);
+
+    fn println(&self, text: &str) {
+        self.print(text);
+        self.print("\n");
+    }
+
+    /// Reports a problem that doesn't end the game, such as a file that couldn't be saved.
+    fn error(&self, text: &str) {
+        self.println(text);
+    }
+
+    /// Starts a new screen.
+    fn clear_screen(&self);
+
+    /// Whether units are drawn in colour.
+    fn uses_color(&self) -> bool;
+
+    /// Rewrites a line of the current screen in place, such as the ticking clock.
+    /// Renderers that can't go back over what they wrote skip it.
+    fn redraw_line(&self, _row: usize, _text: &str) {}
+}
+
+/// Columns the text takes up in a terminal, estimated by hand: CJK and emoji take two, combining
+/// marks, variation selectors and joiners none, anything else one.
+pub fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|glyph| match glyph as u32 {
+            0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+            0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F680..=0x1F6FF
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Writes to the terminal.
+#[derive(Debug, Clone)]
+pub struct StdoutRenderer {
+    color: bool,
+}
+
+impl StdoutRenderer {
+    pub fn new(color: bool) -> StdoutRenderer {
+        StdoutRenderer { color }
+    }
+}
+
+impl Default for StdoutRenderer {
+    fn default() -> Self {
+        StdoutRenderer::new(true)
+    }
+}
+
+impl Renderer for StdoutRenderer {
+    fn print(&self, text: &str) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush(); // Prompts have no newline, but must show before input is read.
+    }
+
+    // Errors go to stderr, so that they show even when the screen is redirected.
+    fn error(&self, text: &str) {
+        let _ = writeln!(io::stderr(), "{}", text);
+    }
+
+    // Plain output has no escape codes at all, screens follow each other as in a file.
+    fn clear_screen(&self) {
+        if !self.color {
+            return;
+        }
+        // Clear the screen and move the cursor to the top-left corner
+        self.print("\x1B[2J\x1B[H");
+    }
+
+    fn uses_color(&self) -> bool {
+        self.color
+    }
+
+    fn redraw_line(&self, row: usize, text: &str) {
+        if !self.color {
+            return;
+        }
+        // Save the cursor, rewrite the row and restore the cursor, so that typing isn't disturbed.
+        self.print(&format!("\x1b7\x1b[{};1H\x1b[2K{}\x1b8", row, text));
+    }
+}
+
+/// Collects output in memory, so that it can be inspected. Clones share the same buffer.
+#[derive(Debug, Clone, Default)]
+pub struct BufferRenderer {
+    buffer: Rc<RefCell<String>>,
+    color: bool,
+}
+
+impl BufferRenderer {
+    pub fn new(color: bool) -> BufferRenderer {
+        BufferRenderer {
+            buffer: Rc::default(),
+            color,
+        }
+    }
+
+    /// Everything written since the last call.
+    pub fn take(&self) -> String {
+        self.buffer.take()
+    }
+}
+
+impl Renderer for BufferRenderer {
+    fn print(&self, text: &str) {
+        self.buffer.borrow_mut().push_str(text);
+    }
+
+    // Screens are kept one after the other.
+    fn clear_screen(&self) {}
+
+    fn uses_color(&self) -> bool {
+        self.color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::{display_width, BufferRenderer, Renderer};
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("R"), 1);
+        assert_eq!(display_width("ab"), 2);
+        assert_eq!(display_width("猫"), 2);
+        assert_eq!(display_width("🍎"), 2);
+        assert_eq!(display_width("❤\u{FE0F}"), 1);
+        assert_eq!(display_width("e\u{301}"), 1);
+        assert_eq!(display_width("猫a"), 3);
+    }
+
+    #[test]
+    fn test_buffer_renderer() {
+        let renderer = BufferRenderer::default();
+        let clone = renderer.clone();
+        renderer.print("Turn - 1");
+        clone.println("");
+        renderer.redraw_line(5, "Turn - 2");
+        clone.error("Could not save scores");
+        assert!(!renderer.uses_color());
+        assert_eq!(renderer.take(), "Turn - 1\nCould not save scores\n");
+        assert_eq!(clone.take(), "");
+    }
+}